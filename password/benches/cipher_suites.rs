@@ -20,8 +20,10 @@ fn cipher_suite(ake: Ake, group: Group, hash: Hash, mhf_hash: Mhf) -> Result<()>
 
 	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
 	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
-	let (_, finalization, _) = client.finish(response)?;
-	server.finish(finalization)
+	let (_, finalization, ..) = client.finish(response)?;
+	server.finish(finalization)?;
+
+	Ok(())
 }
 
 fn cipher_suites(criterion: &mut Criterion) {
@@ -75,7 +75,7 @@ fn client(network: Network, config: ClientConfig) -> Result<()> {
 	network.send(&request)?;
 
 	let response = network.receive()?;
-	let (_, finalization, _) = client.finish(response)?;
+	let (_, finalization, ..) = client.finish(response)?;
 
 	network.send(&finalization)?;
 
@@ -0,0 +1,338 @@
+//! Offline check against known-breached passwords, see [`BreachFilter`] and
+//! [`BreachCorpus`].
+
+use std::{
+	convert::{TryFrom, TryInto},
+	fs,
+	io::ErrorKind,
+	path::{Path, PathBuf},
+};
+
+use sha1::{Digest, Sha1};
+
+use crate::{Config, Error, Result};
+
+/// Size of a SHA-1 hash.
+const HASH_SIZE: usize = 20;
+/// Number of hex characters used as the range prefix of a [`BreachCorpus`].
+const PREFIX_SIZE: usize = 5;
+
+/// Bloom filter of SHA-1 hashed breached passwords.
+///
+/// Build it with `cargo xtask build-breach-filter` from a "Pwned Passwords"
+/// SHA-1 dump and ship it with the client, checking a password doesn't leak
+/// it to anyone.
+///
+/// [`check()`](Self::check) has false positives at the rate the filter was
+/// built with, but no false negatives.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BreachFilter {
+	/// Number of bits set per hash.
+	hashes: u8,
+	/// Bits of the filter.
+	bits: Vec<u8>,
+}
+
+impl BreachFilter {
+	/// Creates an empty [`BreachFilter`] sized for `items` hashes at a false
+	/// positive rate of `false_positive_rate`.
+	///
+	/// # Errors
+	/// [`Error::BreachFilter`] if `items` is `0` or `false_positive_rate` isn't
+	/// between `0` and `1`.
+	#[allow(
+		clippy::as_conversions,
+		clippy::cast_possible_truncation,
+		clippy::cast_precision_loss,
+		clippy::cast_sign_loss,
+		clippy::float_arithmetic
+	)]
+	pub fn new(items: u64, false_positive_rate: f64) -> Result<Self> {
+		if items == 0 || !(false_positive_rate > 0. && false_positive_rate < 1.) {
+			return Err(Error::BreachFilter);
+		}
+
+		let ln2 = std::f64::consts::LN_2;
+		let bits = (-(items as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil();
+		let bytes = ((bits / 8.).ceil() as usize).max(1);
+		let hashes = (bits / items as f64 * ln2).round().clamp(1., 32.) as u8;
+
+		Ok(Self {
+			hashes,
+			bits: vec![0; bytes],
+		})
+	}
+
+	/// Creates a [`BreachFilter`] from bytes created by
+	/// [`to_bytes()`](Self::to_bytes).
+	///
+	/// # Errors
+	/// [`Error::BreachFilter`] if `bytes` are malformed.
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+		match bytes.split_first() {
+			Some((hashes @ 1..=32, bits)) if !bits.is_empty() => Ok(Self {
+				hashes: *hashes,
+				bits: bits.to_vec(),
+			}),
+			_ => Err(Error::BreachFilter),
+		}
+	}
+
+	/// Returns the bytes of this [`BreachFilter`], the number of hashes
+	/// followed by the bits.
+	#[must_use]
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(1 + self.bits.len());
+		bytes.push(self.hashes);
+		bytes.extend_from_slice(&self.bits);
+		bytes
+	}
+
+	/// Inserts `password` into this [`BreachFilter`].
+	pub fn insert<P: AsRef<[u8]>>(&mut self, password: P) {
+		self.insert_hash(&hash(password.as_ref()));
+	}
+
+	/// Inserts the SHA-1 `hash` of a password into this [`BreachFilter`], as
+	/// found in "Pwned Passwords" dumps.
+	#[allow(clippy::integer_arithmetic)]
+	pub fn insert_hash(&mut self, hash: &[u8; HASH_SIZE]) {
+		for index in self.indices(hash) {
+			self.bits[index / 8] |= 1 << (index % 8);
+		}
+	}
+
+	/// Checks if `password` was breached. Pass the same [`Config`] and
+	/// password given to
+	/// [`ClientRegistration::register()`](crate::ClientRegistration::register),
+	/// the password is checked after applying the
+	/// [`Normalization`](crate::Normalization) of `config`.
+	///
+	/// # Errors
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::PasswordBreached`] if `password` is in this [`BreachFilter`]
+	#[allow(clippy::integer_arithmetic)]
+	pub fn check<P: AsRef<[u8]>>(&self, config: Config, password: P) -> Result<()> {
+		let password = config.normalization().normalize(password.as_ref())?;
+		let hash = hash(&password);
+
+		if self
+			.indices(&hash)
+			.all(|index| self.bits[index / 8] & (1 << (index % 8)) != 0)
+		{
+			Err(Error::PasswordBreached)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Returns the bit indices of `hash`, derived with double hashing.
+	#[allow(clippy::integer_arithmetic)]
+	fn indices(&self, hash: &[u8; HASH_SIZE]) -> impl Iterator<Item = usize> {
+		let (first, second) = hash.split_at(8);
+		let first = u64::from_le_bytes(first.try_into().expect("wrong size"));
+		let second = u64::from_le_bytes(second[..8].try_into().expect("wrong size")) | 1;
+		let bits = u64::try_from(self.bits.len()).expect("filter too large") * 8;
+
+		#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+		(0..u64::from(self.hashes))
+			.map(move |index| (first.wrapping_add(index.wrapping_mul(second)) % bits) as usize)
+	}
+}
+
+/// "Pwned Passwords" corpus in the range layout.
+///
+/// One file per five hex character SHA-1 prefix, named `{PREFIX}.txt`,
+/// containing lines of `{SUFFIX}:{COUNT}`. This is the layout produced by the
+/// official downloader.
+///
+/// Only the file of the passwords prefix is read, the corpus can stay on disk.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct BreachCorpus(PathBuf);
+
+impl BreachCorpus {
+	/// Creates a [`BreachCorpus`] stored in the directory `path`.
+	pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+		Self(path.into())
+	}
+
+	/// Returns the directory of this [`BreachCorpus`].
+	#[must_use]
+	pub fn path(&self) -> &Path {
+		&self.0
+	}
+
+	/// Returns how often `password` appeared in breaches, `0` if it didn't.
+	/// The password is looked up after applying the
+	/// [`Normalization`](crate::Normalization) of `config`, see
+	/// [`check()`](Self::check).
+	///
+	/// # Errors
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::BreachCorpus`] if the range file exists but couldn't be read
+	///   or is malformed
+	pub fn count<P: AsRef<[u8]>>(&self, config: Config, password: P) -> Result<u64> {
+		let password = config.normalization().normalize(password.as_ref())?;
+		let hash = hex(&hash(&password));
+		let (prefix, suffix) = hash.split_at(PREFIX_SIZE);
+
+		let range = match fs::read_to_string(self.0.join(format!("{}.txt", prefix))) {
+			Ok(range) => range,
+			Err(error) if error.kind() == ErrorKind::NotFound => return Ok(0),
+			Err(_) => return Err(Error::BreachCorpus),
+		};
+
+		for line in range.lines() {
+			let (line_suffix, count) = line.trim().split_once(':').ok_or(Error::BreachCorpus)?;
+
+			if line_suffix.eq_ignore_ascii_case(suffix) {
+				return count.parse().map_err(|_| Error::BreachCorpus);
+			}
+		}
+
+		Ok(0)
+	}
+
+	/// Checks if `password` was breached. Pass the same [`Config`] and
+	/// password given to
+	/// [`ClientRegistration::register()`](crate::ClientRegistration::register),
+	/// the password is checked after applying the
+	/// [`Normalization`](crate::Normalization) of `config`.
+	///
+	/// # Errors
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::PasswordBreached`] if `password` is in this [`BreachCorpus`]
+	/// - [`Error::BreachCorpus`] if the range file exists but couldn't be read
+	///   or is malformed
+	pub fn check<P: AsRef<[u8]>>(&self, config: Config, password: P) -> Result<()> {
+		if self.count(config, password)? == 0 {
+			Ok(())
+		} else {
+			Err(Error::PasswordBreached)
+		}
+	}
+}
+
+/// Hashes `password` with SHA-1.
+pub(crate) fn hash(password: &[u8]) -> [u8; HASH_SIZE] {
+	Sha1::digest(password).into()
+}
+
+/// Encodes `bytes` as uppercase hex.
+fn hex(bytes: &[u8]) -> String {
+	/// Hex digits.
+	const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+	bytes
+		.iter()
+		.flat_map(|byte| {
+			[
+				DIGITS[usize::from(byte >> 4)],
+				DIGITS[usize::from(byte & 0xf)],
+			]
+		})
+		.map(char::from)
+		.collect()
+}
+
+#[test]
+fn filter() -> anyhow::Result<()> {
+	use crate::Normalization;
+
+	let config = Config::default();
+	let mut filter = BreachFilter::new(1000, 0.001)?;
+
+	for index in 0..1000 {
+		filter.insert(format!("password{}", index));
+	}
+
+	filter.insert_hash(&hash(b"hunter2"));
+
+	assert_eq!(
+		filter.check(config, "password0"),
+		Err(Error::PasswordBreached)
+	);
+	assert_eq!(
+		filter.check(config, "password999"),
+		Err(Error::PasswordBreached)
+	);
+	assert_eq!(
+		filter.check(config, "hunter2"),
+		Err(Error::PasswordBreached)
+	);
+	filter.check(config, "correct horse battery staple")?;
+	filter.check(config, "\u{ff50}assword0")?;
+	assert_eq!(
+		filter.check(
+			config.with_normalization(Normalization::Nfkc),
+			"\u{ff50}assword0"
+		),
+		Err(Error::PasswordBreached)
+	);
+
+	let false_positives = (0..10_000)
+		.filter(|index| {
+			filter
+				.check(config, format!("unbreached{}", index))
+				.is_err()
+		})
+		.count();
+	assert!(false_positives < 50, "{}", false_positives);
+
+	// serialization
+	let bytes = filter.to_bytes();
+	assert_eq!(BreachFilter::from_bytes(&bytes)?, filter);
+
+	// invalid
+	assert_eq!(BreachFilter::new(0, 0.001), Err(Error::BreachFilter));
+	assert_eq!(BreachFilter::new(1000, 1.), Err(Error::BreachFilter));
+	assert_eq!(BreachFilter::new(1000, f64::NAN), Err(Error::BreachFilter));
+	assert_eq!(BreachFilter::from_bytes(&[]), Err(Error::BreachFilter));
+	assert_eq!(BreachFilter::from_bytes(&[0, 1]), Err(Error::BreachFilter));
+	assert_eq!(BreachFilter::from_bytes(&[1]), Err(Error::BreachFilter));
+
+	Ok(())
+}
+
+#[test]
+fn corpus() -> anyhow::Result<()> {
+	use crate::Normalization;
+
+	let config = Config::default();
+	let dir = tempfile::tempdir()?;
+	let path = dir.path();
+
+	let hash = hex(&hash(b"password"));
+	assert_eq!(hash, "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8");
+	fs::write(
+		path.join("5BAA6.txt"),
+		"003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n",
+	)?;
+
+	let corpus = BreachCorpus::new(path);
+	assert_eq!(corpus.path(), path);
+	assert_eq!(corpus.count(config, "password")?, 9_545_824);
+	assert_eq!(
+		corpus.check(config, "password"),
+		Err(Error::PasswordBreached)
+	);
+	assert_eq!(corpus.count(config, "correct horse battery staple")?, 0);
+	corpus.check(config, "correct horse battery staple")?;
+
+	// normalized like during registration
+	let nfkc = config.with_normalization(Normalization::Nfkc);
+	assert_eq!(corpus.count(nfkc, "\u{ff50}assword")?, 9_545_824);
+	assert_eq!(corpus.count(config, "\u{ff50}assword")?, 0);
+	assert_eq!(
+		corpus.check(config.with_normalization(Normalization::OpaqueString), ""),
+		Err(Error::Password)
+	);
+
+	fs::write(path.join("5BAA6.txt"), "malformed")?;
+	assert_eq!(corpus.count(config, "password"), Err(Error::BreachCorpus));
+
+	Ok(())
+}
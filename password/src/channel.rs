@@ -0,0 +1,346 @@
+//! See [`SecureChannel`].
+
+use std::{
+	convert::TryInto,
+	fmt::{self, Debug, Formatter},
+};
+
+use chacha20poly1305::{
+	aead::{Aead, NewAead, Payload},
+	ChaCha20Poly1305, Key, Nonce,
+};
+use zeroize::Zeroizing;
+
+use crate::{crypto, Error, Result, SessionKey};
+
+/// HKDF info used to derive the key for messages sent by the client.
+const CLIENT_INFO: &[u8] = b"custodian-password channel client";
+/// HKDF info used to derive the key for messages sent by the server.
+const SERVER_INFO: &[u8] = b"custodian-password channel server";
+/// HKDF info used to derive the next key when rekeying.
+const REKEY_INFO: &[u8] = b"custodian-password channel rekey";
+/// Size of the header prepended to every message: epoch and sequence number.
+const HEADER_SIZE: usize = 12;
+/// Size of the message count of the previous epoch, appended to the header of
+/// the first message of every epoch.
+const PREVIOUS_SIZE: usize = 8;
+/// Number of messages after which the sending direction is rekeyed
+/// automatically.
+const REKEY_INTERVAL: u64 = 1 << 20;
+
+/// Encrypted and authenticated channel between client and server, established
+/// with the [`SessionKey`] of a successful login.
+///
+/// Each direction uses its own key. Messages carry an epoch and a sequence
+/// number and have to be [`open`](Self::open)ed in the order they were
+/// [`seal`](Self::seal)ed, replayed, reordered or dropped messages are
+/// rejected. Keys are ratcheted forward every 2^20 messages or on
+/// [`rekey()`](Self::rekey). The first message of an epoch carries the number
+/// of messages sent in the previous one, so messages dropped before a rekey
+/// are detected too.
+#[derive(Debug)]
+pub struct SecureChannel {
+	/// State for sending messages.
+	send: Direction,
+	/// State for receiving messages.
+	receive: Direction,
+}
+
+/// State of one direction of a [`SecureChannel`].
+#[derive(Clone)]
+struct Direction {
+	/// Key of the current epoch.
+	key: Zeroizing<[u8; 32]>,
+	/// Current epoch, incremented on every rekey.
+	epoch: u32,
+	/// Sequence number of the next message in this epoch.
+	sequence: u64,
+	/// Number of messages in the previous epoch.
+	previous: u64,
+}
+
+impl Direction {
+	/// Derives the initial state from a [`SessionKey`].
+	fn new(session_key: &SessionKey, info: &[u8]) -> Self {
+		Self {
+			key: crypto::derive_key(session_key.as_slice(), &[], info),
+			epoch: 0,
+			sequence: 0,
+			previous: 0,
+		}
+	}
+
+	/// Returns the header of the next message, the first [`HEADER_SIZE`] bytes
+	/// are also used as the nonce. The first message of an epoch additionally
+	/// carries the number of messages in the previous epoch.
+	fn header(&self) -> Vec<u8> {
+		let mut header = Vec::with_capacity(HEADER_SIZE + PREVIOUS_SIZE);
+		header.extend_from_slice(&self.epoch.to_be_bytes());
+		header.extend_from_slice(&self.sequence.to_be_bytes());
+
+		if self.sequence == 0 {
+			header.extend_from_slice(&self.previous.to_be_bytes());
+		}
+
+		header
+	}
+
+	/// Parses the epoch and sequence number of a header.
+	fn parse(header: &[u8; HEADER_SIZE]) -> (u32, u64) {
+		let (epoch, sequence) = header.split_at(4);
+
+		(
+			u32::from_be_bytes(epoch.try_into().expect("unexpected size")),
+			u64::from_be_bytes(sequence.try_into().expect("unexpected size")),
+		)
+	}
+
+	/// Encrypts `plaintext` with the current key, authenticating `header`.
+	fn encrypt(&self, header: &[u8], plaintext: &[u8]) -> Vec<u8> {
+		ChaCha20Poly1305::new(Key::from_slice(self.key.as_ref()))
+			.encrypt(Nonce::from_slice(&header[..HEADER_SIZE]), Payload {
+				msg: plaintext,
+				aad: header,
+			})
+			.expect("plaintext too long")
+	}
+
+	/// Decrypts `ciphertext` with the current key, authenticating `header`.
+	fn decrypt(&self, header: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+		ChaCha20Poly1305::new(Key::from_slice(self.key.as_ref()))
+			.decrypt(Nonce::from_slice(&header[..HEADER_SIZE]), Payload {
+				msg: ciphertext,
+				aad: header,
+			})
+			.ok()
+	}
+
+	/// Ratchets the key forward and starts a new epoch.
+	///
+	/// # Errors
+	/// [`Error::ChannelExhausted`] if no epochs are left.
+	fn rekey(&mut self) -> Result<()> {
+		self.epoch = self.epoch.checked_add(1).ok_or(Error::ChannelExhausted)?;
+		self.key = crypto::derive_key(self.key.as_ref(), &[], REKEY_INFO);
+		self.previous = self.sequence;
+		self.sequence = 0;
+
+		Ok(())
+	}
+}
+
+impl Debug for Direction {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Direction")
+			.field("epoch", &self.epoch)
+			.field("sequence", &self.sequence)
+			.field("previous", &self.previous)
+			.finish_non_exhaustive()
+	}
+}
+
+impl SecureChannel {
+	/// Creates the clients side of a [`SecureChannel`] from the [`SessionKey`]
+	/// returned by [`ClientLogin::finish()`](crate::ClientLogin::finish).
+	#[must_use]
+	pub fn client(session_key: &SessionKey) -> Self {
+		Self {
+			send: Direction::new(session_key, CLIENT_INFO),
+			receive: Direction::new(session_key, SERVER_INFO),
+		}
+	}
+
+	/// Creates the servers side of a [`SecureChannel`] from the [`SessionKey`]
+	/// returned by [`ServerLogin::finish()`](crate::ServerLogin::finish).
+	#[must_use]
+	pub fn server(session_key: &SessionKey) -> Self {
+		Self {
+			send: Direction::new(session_key, SERVER_INFO),
+			receive: Direction::new(session_key, CLIENT_INFO),
+		}
+	}
+
+	/// Encrypts `plaintext`. The returned message has to be passed to
+	/// [`open()`](Self::open) on the other side.
+	///
+	/// # Errors
+	/// [`Error::ChannelExhausted`] if no epochs are left, a new login is
+	/// required.
+	pub fn seal(&mut self, plaintext: &[u8]) -> Result<Vec<u8>> {
+		if self.send.sequence == REKEY_INTERVAL {
+			self.send.rekey()?;
+		}
+
+		let header = self.send.header();
+		let ciphertext = self.send.encrypt(&header, plaintext);
+		self.send.sequence = self
+			.send
+			.sequence
+			.checked_add(1)
+			.ok_or(Error::ChannelExhausted)?;
+
+		Ok([header, ciphertext].concat())
+	}
+
+	/// Decrypts a `message` produced by [`seal()`](Self::seal) on the other
+	/// side. A rejected message doesn't change the state of the
+	/// [`SecureChannel`].
+	///
+	/// # Errors
+	/// - [`Error::Sequence`] if `message` was replayed, reordered or previous
+	///   messages were dropped, including at the end of the previous epoch
+	/// - [`Error::Channel`] if `message` couldn't be authenticated
+	pub fn open(&mut self, message: &[u8]) -> Result<Vec<u8>> {
+		if message.len() < HEADER_SIZE {
+			return Err(Error::Channel);
+		}
+
+		let (epoch, sequence) = Direction::parse(
+			message[..HEADER_SIZE]
+				.try_into()
+				.map_err(|_| Error::Channel)?,
+		);
+		let header_size = if sequence == 0 {
+			HEADER_SIZE + PREVIOUS_SIZE
+		} else {
+			HEADER_SIZE
+		};
+
+		if message.len() < header_size {
+			return Err(Error::Channel);
+		}
+
+		let (header, ciphertext) = message.split_at(header_size);
+
+		let mut receive = self.receive.clone();
+
+		if epoch != receive.epoch || sequence != receive.sequence {
+			if Some(epoch) == receive.epoch.checked_add(1) && sequence == 0 {
+				receive.rekey()?;
+			} else {
+				return Err(Error::Sequence);
+			}
+		}
+
+		if sequence == 0 && header[HEADER_SIZE..] != receive.previous.to_be_bytes() {
+			return Err(Error::Sequence);
+		}
+
+		let plaintext = receive.decrypt(header, ciphertext).ok_or(Error::Channel)?;
+
+		receive.sequence = receive
+			.sequence
+			.checked_add(1)
+			.ok_or(Error::ChannelExhausted)?;
+		self.receive = receive;
+
+		Ok(plaintext)
+	}
+
+	/// Ratchets the sending key forward. The other side follows automatically
+	/// when receiving the next message.
+	///
+	/// Does nothing if no message was [`seal`](Self::seal)ed since the last
+	/// rekey, the other side only follows one epoch at a time.
+	///
+	/// # Errors
+	/// [`Error::ChannelExhausted`] if no epochs are left, a new login is
+	/// required.
+	pub fn rekey(&mut self) -> Result<()> {
+		if self.send.sequence == 0 {
+			return Ok(());
+		}
+
+		self.send.rekey()
+	}
+}
+
+#[test]
+fn channel() -> anyhow::Result<()> {
+	use arrayvec::ArrayVec;
+
+	let session_key = SessionKey::new(ArrayVec::from([1; 64]));
+	let mut client = SecureChannel::client(&session_key);
+	let mut server = SecureChannel::server(&session_key);
+
+	let first = client.seal(b"first")?;
+	let second = client.seal(b"second")?;
+	let third = client.seal(b"third")?;
+
+	// reordering
+	assert_eq!(server.open(&second), Err(Error::Sequence));
+	assert_eq!(server.open(&first)?, b"first");
+	// replay
+	assert_eq!(server.open(&first), Err(Error::Sequence));
+	// tampering
+	let mut tampered = second.clone();
+	*tampered.last_mut().expect("empty message") ^= 1;
+	assert_eq!(server.open(&tampered), Err(Error::Channel));
+	assert_eq!(server.open(&second[..HEADER_SIZE - 1]), Err(Error::Channel));
+	assert_eq!(server.open(&second)?, b"second");
+	assert_eq!(server.open(&third)?, b"third");
+
+	// other direction
+	let message = server.seal(b"response")?;
+	assert_eq!(
+		SecureChannel::server(&session_key).open(&message),
+		Err(Error::Channel)
+	);
+	assert_eq!(client.open(&message)?, b"response");
+
+	// different session key
+	let mut other = SecureChannel::server(&SessionKey::new(ArrayVec::from([2; 64])));
+	let message = SecureChannel::client(&session_key).seal(b"message")?;
+	assert_eq!(other.open(&message), Err(Error::Channel));
+
+	Ok(())
+}
+
+#[test]
+fn rekey() -> anyhow::Result<()> {
+	use arrayvec::ArrayVec;
+
+	let session_key = SessionKey::new(ArrayVec::from([1; 64]));
+	let mut client = SecureChannel::client(&session_key);
+	let mut server = SecureChannel::server(&session_key);
+
+	let before = client.seal(b"before")?;
+	client.rekey()?;
+	let after = client.seal(b"after")?;
+
+	assert_eq!(server.open(&before)?, b"before");
+	assert_eq!(server.open(&after)?, b"after");
+	assert_eq!(server.open(&before), Err(Error::Sequence));
+
+	// dropping the end of an epoch
+	let kept = client.seal(b"kept")?;
+	let dropped = client.seal(b"dropped")?;
+	client.rekey()?;
+	let next = client.seal(b"next")?;
+	assert_eq!(server.open(&kept)?, b"kept");
+	assert_eq!(server.open(&next), Err(Error::Sequence));
+	// claiming the dropped message was never sent
+	let mut forged = next.clone();
+	forged[HEADER_SIZE + PREVIOUS_SIZE - 1] ^= 1;
+	assert_eq!(server.open(&forged), Err(Error::Channel));
+	assert_eq!(server.open(&dropped)?, b"dropped");
+	assert_eq!(server.open(&next)?, b"next");
+
+	// rekeying twice without sending
+	client.rekey()?;
+	client.rekey()?;
+	assert_eq!(server.open(&client.seal(b"twice")?)?, b"twice");
+
+	// automatic rekey
+	client.send.sequence = REKEY_INTERVAL;
+	server.receive = client.send.clone();
+	let message = client.seal(b"automatic")?;
+	assert_eq!(client.send.sequence, 1);
+	assert_eq!(server.open(&message)?, b"automatic");
+
+	// exhausted
+	client.send.epoch = u32::MAX;
+	assert_eq!(client.rekey(), Err(Error::ChannelExhausted));
+
+	Ok(())
+}
@@ -34,7 +34,8 @@ use sha2::Sha512;
 use sha3::Sha3_256;
 #[cfg(feature = "sha3")]
 use sha3::Sha3_512;
-use zeroize::Zeroize;
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::{Zeroize, Zeroizing};
 
 #[cfg(feature = "blake3")]
 use self::blake3::Blake3;
@@ -43,7 +44,10 @@ use self::p256::P256;
 #[cfg(feature = "pbkdf2")]
 use self::pbkdf2::Pbkdf2;
 use self::public_key::PublicKeyExt;
-use crate::{Error, Result};
+use crate::{crypto, Error, Result};
+
+/// HKDF salt used to derive additional server keys from a [`ServerSetup`].
+const SALT: &[u8] = b"custodian-password server";
 
 /// Wrapper around multiple [`CipherSuite`](ciphersuite::CipherSuite)s to avoid
 /// user-facing generics.
@@ -238,6 +242,7 @@ macro_rules! cipher_suite {
 							message,
 							mut export_key,
 							server_s_pk,
+							..
 						} = result;
 
 						let new_export_key = export_key
@@ -287,7 +292,8 @@ macro_rules! cipher_suite {
 				self,
 				response: LoginResponse,
 				slow_hash: &SlowHashParams,
-			) -> Result<(LoginFinalization, [u8; 33], ArrayVec<u8, 64>)> {
+				context: &[u8],
+			) -> Result<(LoginFinalization, [u8; 33], ArrayVec<u8, 64>, ArrayVec<u8, 64>)> {
 				match (self, response, slow_hash) {
 					$($(#[$attr])? (
 						Self::$cipher_suite(state),
@@ -297,10 +303,15 @@ macro_rules! cipher_suite {
 						let result =
 							state.finish(
 								response,
-								ClientLoginFinishParameters::new(None, None, Some(slow_hash)),
+								ClientLoginFinishParameters::new(
+									Some(context),
+									None,
+									Some(slow_hash),
+								),
 							)?;
 						let ClientLoginFinishResult {
 							message,
+							mut session_key,
 							mut export_key,
 							server_s_pk,
 							..
@@ -312,10 +323,17 @@ macro_rules! cipher_suite {
 							.expect("unexpected size");
 						export_key.zeroize();
 
+						let new_session_key = session_key
+							.as_slice()
+							.try_into()
+							.expect("unexpected size");
+						session_key.zeroize();
+
 						Ok((
 							LoginFinalization::$cipher_suite(message),
 							server_s_pk.into_array(),
 							new_export_key,
+							new_session_key,
 						))
 					})+
 					_ => Err(Error::Config),
@@ -346,6 +364,32 @@ macro_rules! cipher_suite {
 						server_setup.keypair().public().to_array(),)+
 				}
 			}
+
+			/// Derives a key for a specific purpose from the private key and
+			/// OPRF seed.
+			pub(crate) fn derive_key(&self, info: &[u8]) -> Zeroizing<[u8; 32]> {
+				match self {
+					$($(#[$attr])? ServerSetup::$cipher_suite(server_setup) => {
+						let server_setup = Zeroizing::new(server_setup.serialize().to_vec());
+						crypto::derive_key(&server_setup, SALT, info)
+					})+
+				}
+			}
+
+			/// Compares two [`ServerSetup`]s in constant-time.
+			pub(crate) fn ct_eq(&self, other: &Self) -> Choice {
+				match (self, other) {
+					$($(#[$attr])? (
+						ServerSetup::$cipher_suite(server_setup),
+						ServerSetup::$cipher_suite(other),
+					) => {
+						let server_setup = Zeroizing::new(server_setup.serialize().to_vec());
+						let other = Zeroizing::new(other.serialize().to_vec());
+						server_setup.ct_eq(&other)
+					})+
+					_ => Choice::from(0),
+				}
+			}
 		}
 
 		/// [`opaque_ke::ServerRegistration`] wrapper.
@@ -415,33 +459,30 @@ macro_rules! cipher_suite {
 			/// [`opaque_ke::ServerLogin::start()`] wrapper.
 			pub(crate) fn login(
 				setup: &ServerSetup,
-				file: Option<(ServerFile, [u8; 33])>,
+				file: (&ServerFile, [u8; 33]),
 				request: LoginRequest,
+				context: &[u8],
 			) -> Result<(Self, LoginResponse)> {
-				match (setup, request) {
+				match (setup, request, file) {
 					$($(#[$attr])? (
 						ServerSetup::$cipher_suite(server_setup),
 						LoginRequest::$cipher_suite(request),
+						(ServerFile::$cipher_suite(file), public_key),
 					) => {
-						let file = match file {
-							Some((ServerFile::$cipher_suite(file), public_key)) => {
-								if !server_setup.keypair().public().is_array(public_key) {
-									return Err(Error::ServerFile);
-								}
-
-								Some(file)
-							},
-							Some(_) => return Err(Error::Config),
-							None => None,
-						};
+						if !server_setup.keypair().public().is_array(public_key) {
+							return Err(Error::ServerFile);
+						}
 
 						let result = opaque_ke::ServerLogin::start(
 							&mut OsRng,
 							server_setup,
-							file,
+							Some(file.clone()),
 							request,
 							&[],
-							ServerLoginStartParameters::default(),
+							ServerLoginStartParameters {
+								context: Some(context),
+								..ServerLoginStartParameters::default()
+							},
 						)?;
 						let ServerLoginStartResult { state, message } = result;
 						Ok((
@@ -454,16 +495,23 @@ macro_rules! cipher_suite {
 			}
 
 			/// [`opaque_ke::ClientLogin::finish()`] wrapper.
-			pub(crate) fn finish(self, finalization: LoginFinalization) -> Result<()> {
+			pub(crate) fn finish(self, finalization: LoginFinalization
+			) -> Result<ArrayVec<u8, 64>> {
 				match (self, finalization) {
 					$($(#[$attr])? (
 						Self::$cipher_suite(state),
 						LoginFinalization::$cipher_suite(finalization),
 					) => {
 						let result = state.finish(finalization)?;
-						let ServerLoginFinishResult { .. } = result;
+						let ServerLoginFinishResult { mut session_key } = result;
+
+						let new_session_key = session_key
+							.as_slice()
+							.try_into()
+							.expect("unexpected size");
+						session_key.zeroize();
 
-						Ok(())
+						Ok(new_session_key)
 					})+
 					_ => Err(Error::Config),
 				}
@@ -2,6 +2,7 @@
 
 use curve25519_dalek::{montgomery::MontgomeryPoint, ristretto::RistrettoPoint};
 use opaque_ke::keypair::PublicKey;
+use subtle::ConstantTimeEq;
 
 /// Utility trait to help convert and compare [`opaque_ke::keypair::PublicKey`]
 /// to `[u8; 33]`.
@@ -29,7 +30,7 @@ impl PublicKeyExt for PublicKey<RistrettoPoint> {
 	}
 
 	fn is_array(&self, key: [u8; 33]) -> bool {
-		&key[..32] == self.as_slice()
+		key[..32].ct_eq(self.as_slice()).into()
 	}
 }
 
@@ -46,7 +47,7 @@ impl PublicKeyExt for PublicKey<MontgomeryPoint> {
 	}
 
 	fn is_array(&self, key: [u8; 33]) -> bool {
-		&key[..32] == self.as_slice()
+		key[..32].ct_eq(self.as_slice()).into()
 	}
 }
 
@@ -61,6 +62,6 @@ impl PublicKeyExt for PublicKey<super::p256::P256> {
 	}
 
 	fn is_array(&self, key: [u8; 33]) -> bool {
-		key == self.as_slice()
+		key[..].ct_eq(self.as_slice()).into()
 	}
 }
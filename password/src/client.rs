@@ -2,12 +2,17 @@
 
 //! OPAQUE client side handling.
 
+use std::fmt::{self, Debug, Formatter};
+
 use opaque_ke::errors::ProtocolError;
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroize;
 
 use crate::{
-	cipher_suite, Config, Error, ExportKey, LoginFinalization, LoginRequest, LoginResponse,
-	PublicKey, RegistrationFinalization, RegistrationRequest, RegistrationResponse, Result,
+	cipher_suite, crypto, escrow::Escrow, recipient::RecipientPublicKey, Config, Error, ExportKey,
+	LoginFinalization, LoginRequest, LoginResponse, PublicKey, RegistrationFinalization,
+	RegistrationRequest, RegistrationResponse, Result, SessionKey,
 };
 
 /// Client configuration.
@@ -17,6 +22,8 @@ pub struct ClientConfig {
 	config: Config,
 	/// Server key pair.
 	public_key: Option<PublicKey>,
+	/// Administrators public key to escrow to.
+	escrow: Option<RecipientPublicKey>,
 }
 
 impl ClientConfig {
@@ -36,7 +43,25 @@ impl ClientConfig {
 			}
 		}
 
-		Ok(Self { config, public_key })
+		Ok(Self {
+			config,
+			public_key,
+			escrow: None,
+		})
+	}
+
+	/// Enables escrow to an administrators [`RecipientPublicKey`]. An
+	/// [`Escrow`] will be sent alongside every [`RegistrationFinalization`]
+	/// and [`LoginFinalization`], allowing the administrator to unlock
+	/// [`KeySlot::Escrow`](crate::KeySlot::Escrow).
+	///
+	/// # Caution
+	/// This gives the administrator access to all data protected by
+	/// [`WrappedKey`](crate::WrappedKey)s with an escrow slot.
+	#[must_use]
+	pub const fn with_escrow(mut self, public_key: RecipientPublicKey) -> Self {
+		self.escrow = Some(public_key);
+		self
 	}
 
 	/// Returns the [`Config`] associated with this [`ClientConfig`].
@@ -50,18 +75,61 @@ impl ClientConfig {
 	pub const fn public_key(&self) -> Option<PublicKey> {
 		self.public_key
 	}
+
+	/// Returns the administrators [`RecipientPublicKey`] if escrow is enabled.
+	/// See [`with_escrow()`](Self::with_escrow).
+	#[must_use]
+	pub const fn escrow(&self) -> Option<RecipientPublicKey> {
+		self.escrow
+	}
+
+	/// Returns a [`ClientConfig`] to register and login
+	/// [`RecoveryCode`](crate::RecoveryCode)s. See [`Config::to_recovery()`].
+	/// Escrow is disabled.
+	#[must_use]
+	pub fn to_recovery(self) -> Self {
+		let config = self.config.to_recovery();
+
+		Self {
+			config,
+			public_key: self
+				.public_key
+				.map(|public_key| PublicKey::new(config, public_key.key)),
+			escrow: None,
+		}
+	}
 }
 
 /// Holds the state of a registration process. See [`register`](Self::register).
 #[must_use = "Use `finish()` to complete the registration process"]
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ClientRegistration {
 	/// [`ClientConfig`] of this [`ClientRegistration`].
 	config: ClientConfig,
 	/// Client registration state.
 	state: cipher_suite::ClientRegistration,
+	/// Password to check against the [`PasswordPolicy`](crate::PasswordPolicy)
+	/// of the server.
+	candidate: Candidate,
+}
+
+/// Normalized password and banned substrings, kept by [`ClientRegistration`]
+/// until the [`PasswordPolicy`](crate::PasswordPolicy) of the server is known.
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+struct Candidate {
+	/// Normalized password.
+	password: Vec<u8>,
+	/// Substrings the password can't contain.
+	banned: Vec<String>,
 }
 
+crypto::impl_secret!(Candidate, |this, other| this
+	.password
+	.as_slice()
+	.ct_eq(other.password.as_slice())
+	& Choice::from(u8::from(this.banned == other.banned)));
+
 impl ClientRegistration {
 	/// Returns the [`ClientConfig`] associated with this
 	/// [`ClientRegistration`].
@@ -74,21 +142,54 @@ impl ClientRegistration {
 	/// has to be send to the server to drive the registration process. See
 	/// [`ServerRegistration::register()`](crate::ServerRegistration::register).
 	///
+	/// The password is checked against the
+	/// [`PasswordPolicy`](crate::PasswordPolicy) of the server in
+	/// [`finish()`](Self::finish).
+	///
 	/// # Errors
-	/// [`Error::Opaque`] on internal OPAQUE error.
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::Opaque`] on internal OPAQUE error
 	pub fn register<P: AsRef<[u8]>>(
 		config: ClientConfig,
 		password: P,
 	) -> Result<(Self, RegistrationRequest)> {
-		let (state, message) = cipher_suite::ClientRegistration::register(
-			config.config.cipher_suite,
-			password.as_ref(),
-		)?;
+		Self::register_with_banned(config, password, &[])
+	}
 
-		Ok((Self { config, state }, RegistrationRequest {
-			config: config.config,
-			message,
-		}))
+	/// Starts the registration process like [`register()`](Self::register),
+	/// but [`finish()`](Self::finish) additionally rejects passwords
+	/// containing any of the `banned` substrings, e.g. the user name. With the
+	/// `zxcvbn` crate feature they are also considered when estimating the
+	/// `Strength`.
+	///
+	/// # Errors
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn register_with_banned<P: AsRef<[u8]>>(
+		config: ClientConfig,
+		password: P,
+		banned: &[&str],
+	) -> Result<(Self, RegistrationRequest)> {
+		let password = config.config.normalization().normalize(password.as_ref())?;
+		let (state, message) =
+			cipher_suite::ClientRegistration::register(config.config.cipher_suite, &password)?;
+
+		Ok((
+			Self {
+				config,
+				state,
+				candidate: Candidate {
+					password: password.to_vec(),
+					banned: banned.iter().map(|&banned| banned.to_owned()).collect(),
+				},
+			},
+			RegistrationRequest {
+				config: config.config,
+				message,
+			},
+		))
 	}
 
 	/// Finishes the registration process. The returned
@@ -102,11 +203,19 @@ impl ClientRegistration {
 	/// [`ExportKey`] can be used to encrypt data and store it safely on
 	/// the server. See [`ExportKey`] for more details.
 	///
+	/// If escrow is enabled, [`RegistrationFinalization`] contains an
+	/// [`Escrow`], see [`ClientConfig::with_escrow()`].
+	///
 	/// # Errors
 	/// - [`Error::Config`] if [`ClientRegistration`] and
 	///   [`RegistrationResponse`] were not created with the same [`Config`]
+	/// - [`Error::PasswordTooShort`], [`Error::PasswordTooLong`],
+	///   [`Error::PasswordBanned`] or `Error::PasswordWeak` if the password
+	///   violates the [`PasswordPolicy`](crate::PasswordPolicy) of the server
 	/// - [`Error::InvalidServer`] if the public key given in
 	///   [`register()`](Self::register) does not match the servers public key
+	/// - [`Error::RecipientPublicKey`] if the escrow [`RecipientPublicKey`] is
+	///   invalid
 	/// - [`Error::Opaque`] on internal OPAQUE error
 	pub fn finish(
 		self,
@@ -116,12 +225,30 @@ impl ClientRegistration {
 			return Err(Error::Config);
 		}
 
+		let banned: Vec<_> = self.candidate.banned.iter().map(String::as_str).collect();
+		response.policy.check(&self.candidate.password, &banned)?;
+
+		self.finish_unchecked(response)
+	}
+
+	/// Finishes the registration process like [`finish()`](Self::finish),
+	/// without checking the [`PasswordPolicy`](crate::PasswordPolicy) of the
+	/// server. Used when the password was already accepted otherwise, e.g. by
+	/// a legacy system during a [`ClientMigration`](crate::ClientMigration).
+	pub(crate) fn finish_unchecked(
+		self,
+		response: RegistrationResponse,
+	) -> Result<(ClientFile, RegistrationFinalization, ExportKey)> {
+		if self.config.config != response.config {
+			return Err(Error::Config);
+		}
+
 		let (message, new_public_key, export_key) = self
 			.state
 			.finish(response.message, &self.config.config.mhf().to_slow_hash())?;
 
 		let public_key = if let Some(public_key) = self.config.public_key {
-			if public_key.key != new_public_key {
+			if bool::from(!public_key.key[..].ct_eq(&new_public_key)) {
 				return Err(Error::InvalidServer);
 			}
 
@@ -130,17 +257,33 @@ impl ClientRegistration {
 			PublicKey::new(self.config.config, new_public_key)
 		};
 
+		let export_key = ExportKey::new(export_key);
+		let escrow = self
+			.config
+			.escrow
+			.map(|escrow| Escrow::new(escrow, &export_key))
+			.transpose()?;
+
 		Ok((
 			ClientFile(public_key),
 			RegistrationFinalization {
 				config: self.config.config,
 				message,
+				escrow,
 			},
-			ExportKey::new(export_key),
+			export_key,
 		))
 	}
 }
 
+impl Debug for ClientRegistration {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ClientRegistration")
+			.field("config", &self.config)
+			.finish_non_exhaustive()
+	}
+}
+
 /// Use this to enable server validation during login.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 pub struct ClientFile(PublicKey);
@@ -161,7 +304,7 @@ impl ClientFile {
 
 /// Holds the state of a login process. See [`login`](Self::login).
 #[must_use = "Does nothing if not `finish`ed"]
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
 pub struct ClientLogin {
 	/// [`ClientConfig`] of this [`ClientLogin`].
 	config: ClientConfig,
@@ -188,6 +331,8 @@ impl ClientLogin {
 	///   created with the same [`Config`]
 	/// - [`Error::ConfigPublicKey`] if [`PublicKey`] in [`ClientConfig`] and
 	///   [`ClientFile`] don't match
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
 	/// - [`Error::Opaque`] on internal OPAQUE error
 	pub fn login<P: AsRef<[u8]>>(
 		mut config: ClientConfig,
@@ -208,8 +353,9 @@ impl ClientLogin {
 			}
 		}
 
+		let password = config.config.normalization().normalize(password.as_ref())?;
 		let (state, message) =
-			cipher_suite::ClientLogin::login(config.config.cipher_suite, password.as_ref())?;
+			cipher_suite::ClientLogin::login(config.config.cipher_suite, &password)?;
 
 		Ok((Self { config, state }, LoginRequest {
 			config: config.config,
@@ -226,32 +372,53 @@ impl ClientLogin {
 	/// [`ExportKey`] can be used to encrypt data and store it on safely on
 	/// the server. See [`ExportKey`] for more details.
 	///
+	/// [`SessionKey`] is shared with the server after it
+	/// [`finish`](crate::ServerLogin::finish)ed the login process, see
+	/// [`SecureChannel`](crate::SecureChannel).
+	///
+	/// If escrow is enabled, [`LoginFinalization`] contains an [`Escrow`], see
+	/// [`ClientConfig::with_escrow()`].
+	///
 	/// # Errors
 	/// - [`Error::Config`] if [`ClientLogin`] and [`LoginResponse`] were not
 	///   created with the same [`Config`]
 	/// - [`Error::Credentials`] if credentials don't match
 	/// - [`Error::InvalidServer`] if the public key given in
 	///   [`login()`](Self::login) does not match the servers public key
+	/// - [`Error::RecipientPublicKey`] if the escrow [`RecipientPublicKey`] is
+	///   invalid
 	/// - [`Error::Opaque`] on internal OPAQUE error
 	pub fn finish(
 		self,
 		response: LoginResponse,
-	) -> Result<(ClientFile, LoginFinalization, ExportKey)> {
+	) -> Result<(ClientFile, LoginFinalization, ExportKey, SessionKey)> {
+		self.finish_with_context(response, &[])
+	}
+
+	/// [`finish()`](Self::finish) binding `context` into the transcript, has to
+	/// match the `context` passed to
+	/// [`ServerLogin::login_with_context()`](crate::ServerLogin::login_with_context).
+	pub(crate) fn finish_with_context(
+		self,
+		response: LoginResponse,
+		context: &[u8],
+	) -> Result<(ClientFile, LoginFinalization, ExportKey, SessionKey)> {
 		if self.config.config != response.config {
 			return Err(Error::Config);
 		}
 
-		let (message, new_public_key, export_key) = match self
-			.state
-			.finish(response.message, &self.config.config.mhf().to_slow_hash())
-		{
+		let (message, new_public_key, export_key, session_key) = match self.state.finish(
+			response.message,
+			&self.config.config.mhf().to_slow_hash(),
+			context,
+		) {
 			Ok(result) => result,
 			Err(Error::Opaque(ProtocolError::InvalidLoginError)) => return Err(Error::Credentials),
 			Err(error) => return Err(error),
 		};
 
 		let public_key = if let Some(public_key) = self.config.public_key {
-			if public_key.key != new_public_key {
+			if bool::from(!public_key.key[..].ct_eq(&new_public_key)) {
 				return Err(Error::InvalidServer);
 			}
 
@@ -260,13 +427,30 @@ impl ClientLogin {
 			PublicKey::new(self.config.config, new_public_key)
 		};
 
+		let export_key = ExportKey::new(export_key);
+		let escrow = self
+			.config
+			.escrow
+			.map(|escrow| Escrow::new(escrow, &export_key))
+			.transpose()?;
+
 		Ok((
 			ClientFile(public_key),
 			LoginFinalization {
 				config: self.config.config,
 				message,
+				escrow,
 			},
-			ExportKey::new(export_key),
+			export_key,
+			SessionKey::new(session_key),
 		))
 	}
 }
+
+impl Debug for ClientLogin {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ClientLogin")
+			.field("config", &self.config)
+			.finish_non_exhaustive()
+	}
+}
@@ -2,12 +2,16 @@
 
 use std::{
 	fmt::{self, Debug, Formatter},
+	mem,
 	num::NonZeroU32,
+	str,
 };
 
 use argon2::{Algorithm, Argon2, Params, Version};
 use deranged::U32;
 use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroizing;
 
 #[cfg(feature = "pbkdf2")]
 use crate::cipher_suite::pbkdf2::Pbkdf2;
@@ -15,6 +19,8 @@ use crate::{
 	cipher_suite::{CipherSuite, SlowHashParams},
 	Error, Result,
 };
+#[cfg(feature = "zxcvbn")]
+use crate::{Score, Strength};
 
 /// Common password configuration between server and client.
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
@@ -23,6 +29,8 @@ pub struct Config {
 	pub(crate) cipher_suite: CipherSuite,
 	/// [`Mhf`] configuration.
 	mhf: Mhf,
+	/// [`Normalization`] applied to passwords.
+	normalization: Normalization,
 }
 
 impl Default for Config {
@@ -38,191 +46,88 @@ impl Default for Config {
 
 impl Config {
 	/// Builds new [`Config`].
-	#[allow(clippy::too_many_lines)]
 	#[must_use]
 	pub const fn new(ake: Ake, group: Group, hash: Hash, mhf: Mhf) -> Self {
 		#[allow(clippy::enum_glob_use)]
 		use self::{CipherSuite::*, Group::*, Hash::*, Mhf::*};
 
-		match (ake, group, hash, mhf) {
-			(Ake::Ristretto255, Ristretto255, Sha2, mhf @ Argon2(_)) => Self {
-				cipher_suite: Ristretto255Sha2Argon2,
-				mhf,
-			},
+		let cipher_suite = match (ake, group, hash, mhf) {
+			(Ake::Ristretto255, Ristretto255, Sha2, Argon2(_)) => Ristretto255Sha2Argon2,
 			#[cfg(feature = "pbkdf2")]
-			(Ake::Ristretto255, Ristretto255, Sha2, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: Ristretto255Sha2Pbkdf2,
-				mhf,
-			},
+			(Ake::Ristretto255, Ristretto255, Sha2, Pbkdf2(_)) => Ristretto255Sha2Pbkdf2,
 			#[cfg(feature = "sha3")]
-			(Ake::Ristretto255, Ristretto255, Sha3, mhf @ Argon2(_)) => Self {
-				cipher_suite: Ristretto255Sha3Argon2,
-				mhf,
-			},
+			(Ake::Ristretto255, Ristretto255, Sha3, Argon2(_)) => Ristretto255Sha3Argon2,
 			#[cfg(all(feature = "sha3", feature = "pbkdf2"))]
-			(Ake::Ristretto255, Ristretto255, Sha3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: Ristretto255Sha3Pbkdf2,
-				mhf,
-			},
+			(Ake::Ristretto255, Ristretto255, Sha3, Pbkdf2(_)) => Ristretto255Sha3Pbkdf2,
 			#[cfg(feature = "blake3")]
-			(Ake::Ristretto255, Ristretto255, Blake3, mhf @ Argon2(_)) => Self {
-				cipher_suite: Ristretto255Blake3Argon2,
-				mhf,
-			},
+			(Ake::Ristretto255, Ristretto255, Blake3, Argon2(_)) => Ristretto255Blake3Argon2,
 			#[cfg(all(feature = "blake3", feature = "pbkdf2"))]
-			(Ake::Ristretto255, Ristretto255, Blake3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: Ristretto255Blake3Pbkdf2,
-				mhf,
-			},
-			(Ake::X25519, Ristretto255, Sha2, mhf @ Argon2(_)) => Self {
-				cipher_suite: X25519Ristretto255Sha2Argon2,
-				mhf,
-			},
+			(Ake::Ristretto255, Ristretto255, Blake3, Pbkdf2(_)) => Ristretto255Blake3Pbkdf2,
+			(Ake::X25519, Ristretto255, Sha2, Argon2(_)) => X25519Ristretto255Sha2Argon2,
 			#[cfg(feature = "pbkdf2")]
-			(Ake::X25519, Ristretto255, Sha2, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: X25519Ristretto255Sha2Pbkdf2,
-				mhf,
-			},
+			(Ake::X25519, Ristretto255, Sha2, Pbkdf2(_)) => X25519Ristretto255Sha2Pbkdf2,
 			#[cfg(feature = "sha3")]
-			(Ake::X25519, Ristretto255, Sha3, mhf @ Argon2(_)) => Self {
-				cipher_suite: X25519Ristretto255Sha3Argon2,
-				mhf,
-			},
+			(Ake::X25519, Ristretto255, Sha3, Argon2(_)) => X25519Ristretto255Sha3Argon2,
 			#[cfg(all(feature = "sha3", feature = "pbkdf2"))]
-			(Ake::X25519, Ristretto255, Sha3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: X25519Ristretto255Sha3Pbkdf2,
-				mhf,
-			},
+			(Ake::X25519, Ristretto255, Sha3, Pbkdf2(_)) => X25519Ristretto255Sha3Pbkdf2,
 			#[cfg(feature = "blake3")]
-			(Ake::X25519, Ristretto255, Blake3, mhf @ Argon2(_)) => Self {
-				cipher_suite: X25519Ristretto255Blake3Argon2,
-				mhf,
-			},
+			(Ake::X25519, Ristretto255, Blake3, Argon2(_)) => X25519Ristretto255Blake3Argon2,
 			#[cfg(all(feature = "blake3", feature = "pbkdf2"))]
-			(Ake::X25519, Ristretto255, Blake3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: X25519Ristretto255Blake3Pbkdf2,
-				mhf,
-			},
+			(Ake::X25519, Ristretto255, Blake3, Pbkdf2(_)) => X25519Ristretto255Blake3Pbkdf2,
 			#[cfg(feature = "p256")]
-			(Ake::P256, Ristretto255, Sha2, mhf @ Argon2(_)) => Self {
-				cipher_suite: P256Ristretto255Sha2Argon2,
-				mhf,
-			},
+			(Ake::P256, Ristretto255, Sha2, Argon2(_)) => P256Ristretto255Sha2Argon2,
 			#[cfg(all(feature = "p256", feature = "pbkdf2"))]
-			(Ake::P256, Ristretto255, Sha2, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: P256Ristretto255Sha2Pbkdf2,
-				mhf,
-			},
+			(Ake::P256, Ristretto255, Sha2, Pbkdf2(_)) => P256Ristretto255Sha2Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "sha3"))]
-			(Ake::P256, Ristretto255, Sha3, mhf @ Argon2(_)) => Self {
-				cipher_suite: P256Ristretto255Sha3Argon2,
-				mhf,
-			},
+			(Ake::P256, Ristretto255, Sha3, Argon2(_)) => P256Ristretto255Sha3Argon2,
 			#[cfg(all(feature = "p256", feature = "sha3", feature = "pbkdf2"))]
-			(Ake::P256, Ristretto255, Sha3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: P256Ristretto255Sha3Pbkdf2,
-				mhf,
-			},
+			(Ake::P256, Ristretto255, Sha3, Pbkdf2(_)) => P256Ristretto255Sha3Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "blake3"))]
-			(Ake::P256, Ristretto255, Blake3, mhf @ Argon2(_)) => Self {
-				cipher_suite: P256Ristretto255Blake3Argon2,
-				mhf,
-			},
+			(Ake::P256, Ristretto255, Blake3, Argon2(_)) => P256Ristretto255Blake3Argon2,
 			#[cfg(all(feature = "p256", feature = "blake3", feature = "pbkdf2"))]
-			(Ake::P256, Ristretto255, Blake3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: P256Ristretto255Blake3Pbkdf2,
-				mhf,
-			},
+			(Ake::P256, Ristretto255, Blake3, Pbkdf2(_)) => P256Ristretto255Blake3Pbkdf2,
 			#[cfg(feature = "p256")]
-			(Ake::P256, P256, Sha2, mhf @ Argon2(_)) => Self {
-				cipher_suite: P256Sha2Argon2,
-				mhf,
-			},
+			(Ake::P256, P256, Sha2, Argon2(_)) => P256Sha2Argon2,
 			#[cfg(all(feature = "p256", feature = "pbkdf2"))]
-			(Ake::P256, P256, Sha2, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: P256Sha2Pbkdf2,
-				mhf,
-			},
+			(Ake::P256, P256, Sha2, Pbkdf2(_)) => P256Sha2Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "sha3"))]
-			(Ake::P256, P256, Sha3, mhf @ Argon2(_)) => Self {
-				cipher_suite: P256Sha3Argon2,
-				mhf,
-			},
+			(Ake::P256, P256, Sha3, Argon2(_)) => P256Sha3Argon2,
 			#[cfg(all(feature = "p256", feature = "sha3", feature = "pbkdf2"))]
-			(Ake::P256, P256, Sha3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: P256Sha3Pbkdf2,
-				mhf,
-			},
+			(Ake::P256, P256, Sha3, Pbkdf2(_)) => P256Sha3Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "blake3"))]
-			(Ake::P256, P256, Blake3, mhf @ Argon2(_)) => Self {
-				cipher_suite: P256Blake3Argon2,
-				mhf,
-			},
+			(Ake::P256, P256, Blake3, Argon2(_)) => P256Blake3Argon2,
 			#[cfg(all(feature = "p256", feature = "blake3", feature = "pbkdf2"))]
-			(Ake::P256, P256, Blake3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: P256Blake3Pbkdf2,
-				mhf,
-			},
+			(Ake::P256, P256, Blake3, Pbkdf2(_)) => P256Blake3Pbkdf2,
 			#[cfg(feature = "p256")]
-			(Ake::Ristretto255, P256, Sha2, mhf @ Argon2(_)) => Self {
-				cipher_suite: Ristretto255P256Sha2Argon2,
-				mhf,
-			},
+			(Ake::Ristretto255, P256, Sha2, Argon2(_)) => Ristretto255P256Sha2Argon2,
 			#[cfg(all(feature = "p256", feature = "pbkdf2"))]
-			(Ake::Ristretto255, P256, Sha2, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: Ristretto255P256Sha2Pbkdf2,
-				mhf,
-			},
+			(Ake::Ristretto255, P256, Sha2, Pbkdf2(_)) => Ristretto255P256Sha2Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "sha3"))]
-			(Ake::Ristretto255, P256, Sha3, mhf @ Argon2(_)) => Self {
-				cipher_suite: Ristretto255P256Sha3Argon2,
-				mhf,
-			},
+			(Ake::Ristretto255, P256, Sha3, Argon2(_)) => Ristretto255P256Sha3Argon2,
 			#[cfg(all(feature = "p256", feature = "sha3", feature = "pbkdf2"))]
-			(Ake::Ristretto255, P256, Sha3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: Ristretto255P256Sha3Pbkdf2,
-				mhf,
-			},
+			(Ake::Ristretto255, P256, Sha3, Pbkdf2(_)) => Ristretto255P256Sha3Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "blake3"))]
-			(Ake::Ristretto255, P256, Blake3, mhf @ Argon2(_)) => Self {
-				cipher_suite: Ristretto255P256Blake3Argon2,
-				mhf,
-			},
+			(Ake::Ristretto255, P256, Blake3, Argon2(_)) => Ristretto255P256Blake3Argon2,
 			#[cfg(all(feature = "p256", feature = "blake3", feature = "pbkdf2"))]
-			(Ake::Ristretto255, P256, Blake3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: Ristretto255P256Blake3Pbkdf2,
-				mhf,
-			},
+			(Ake::Ristretto255, P256, Blake3, Pbkdf2(_)) => Ristretto255P256Blake3Pbkdf2,
 			#[cfg(feature = "p256")]
-			(Ake::X25519, P256, Sha2, mhf @ Argon2(_)) => Self {
-				cipher_suite: X25519P256Sha2Argon2,
-				mhf,
-			},
+			(Ake::X25519, P256, Sha2, Argon2(_)) => X25519P256Sha2Argon2,
 			#[cfg(all(feature = "p256", feature = "pbkdf2"))]
-			(Ake::X25519, P256, Sha2, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: X25519P256Sha2Pbkdf2,
-				mhf,
-			},
+			(Ake::X25519, P256, Sha2, Pbkdf2(_)) => X25519P256Sha2Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "sha3"))]
-			(Ake::X25519, P256, Sha3, mhf @ Argon2(_)) => Self {
-				cipher_suite: X25519P256Sha3Argon2,
-				mhf,
-			},
+			(Ake::X25519, P256, Sha3, Argon2(_)) => X25519P256Sha3Argon2,
 			#[cfg(all(feature = "p256", feature = "sha3", feature = "pbkdf2"))]
-			(Ake::X25519, P256, Sha3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: X25519P256Sha3Pbkdf2,
-				mhf,
-			},
+			(Ake::X25519, P256, Sha3, Pbkdf2(_)) => X25519P256Sha3Pbkdf2,
 			#[cfg(all(feature = "p256", feature = "blake3"))]
-			(Ake::X25519, P256, Blake3, mhf @ Argon2(_)) => Self {
-				cipher_suite: X25519P256Blake3Argon2,
-				mhf,
-			},
+			(Ake::X25519, P256, Blake3, Argon2(_)) => X25519P256Blake3Argon2,
 			#[cfg(all(feature = "p256", feature = "blake3", feature = "pbkdf2"))]
-			(Ake::X25519, P256, Blake3, mhf @ Pbkdf2(_)) => Self {
-				cipher_suite: X25519P256Blake3Pbkdf2,
-				mhf,
-			},
+			(Ake::X25519, P256, Blake3, Pbkdf2(_)) => X25519P256Blake3Pbkdf2,
+		};
+
+		Self {
+			cipher_suite,
+			mhf,
+			normalization: Normalization::None,
 		}
 	}
 
@@ -398,6 +303,33 @@ impl Config {
 	pub const fn mhf(self) -> Mhf {
 		self.mhf
 	}
+
+	/// Returns [`Normalization`] of this [`Config`].
+	#[must_use]
+	pub const fn normalization(self) -> Normalization {
+		self.normalization
+	}
+
+	/// Returns a [`Config`] applying `normalization` to passwords. Client
+	/// and server have to use the same [`Normalization`], otherwise
+	/// [`Error::Config`] is returned.
+	#[must_use]
+	pub const fn with_normalization(mut self, normalization: Normalization) -> Self {
+		self.normalization = normalization;
+		self
+	}
+
+	/// Returns a [`Config`] for [`RecoveryCode`](crate::RecoveryCode)s. Uses
+	/// the same [`CipherSuite`] with the cheapest [`Mhf`] parameters, recovery
+	/// codes have enough entropy on their own.
+	#[must_use]
+	pub fn to_recovery(self) -> Self {
+		Self {
+			cipher_suite: self.cipher_suite,
+			mhf: self.mhf.to_recovery(),
+			normalization: self.normalization,
+		}
+	}
 }
 
 /// Authenticated Key-Exchange for OPAQUE.
@@ -537,6 +469,23 @@ impl Mhf {
 			Self::Pbkdf2(config) => SlowHashParams::Pbkdf2(Pbkdf2(config)),
 		}
 	}
+
+	/// Returns the cheapest parameters of the same [`Mhf`].
+	fn to_recovery(self) -> Self {
+		match self {
+			Self::Argon2(config) => Self::Argon2(Argon2Params {
+				algorithm: config.algorithm,
+				m_cost: U32::new(Params::MIN_M_COST).expect("unexpected cost"),
+				t_cost: NonZeroU32::new(Params::MIN_T_COST).expect("unexpected cost"),
+				p_cost: U32::new(Params::MIN_P_COST).expect("unexpected cost"),
+			}),
+			#[cfg(feature = "pbkdf2")]
+			Self::Pbkdf2(config) => Self::Pbkdf2(Pbkdf2Params {
+				hash: config.hash,
+				rounds: NonZeroU32::new(1).expect("unexpected value"),
+			}),
+		}
+	}
 }
 
 /// Configuration for [`Mhf::Argon2`].
@@ -678,3 +627,226 @@ impl Default for Pbkdf2Hash {
 		Self::Sha256
 	}
 }
+
+/// Unicode normalization applied to passwords before they are used, so the
+/// same password typed on different platforms yields the same bytes.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Normalization {
+	/// Passwords are used as is.
+	None,
+	/// Unicode Normalization Form KC.
+	Nfkc,
+	/// PRECIS `OpaqueString` profile as defined in RFC 8265: non-ASCII spaces
+	/// are mapped to U+0020, followed by Unicode Normalization Form C. Empty
+	/// passwords and control characters are rejected.
+	OpaqueString,
+}
+
+impl Default for Normalization {
+	fn default() -> Self {
+		Self::None
+	}
+}
+
+impl Normalization {
+	/// Normalizes `password`.
+	///
+	/// # Errors
+	/// [`Error::Password`] if `password` isn't valid UTF-8 or is rejected by
+	/// the [`Normalization`].
+	pub(crate) fn normalize(self, password: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+		match self {
+			Self::None => Ok(Zeroizing::new(password.to_vec())),
+			Self::Nfkc => {
+				let password = str::from_utf8(password).map_err(|_| Error::Password)?;
+				Ok(collect(password.len(), password.nfkc()))
+			}
+			Self::OpaqueString => {
+				let password = str::from_utf8(password).map_err(|_| Error::Password)?;
+
+				if password.is_empty() || password.chars().any(char::is_control) {
+					return Err(Error::Password);
+				}
+
+				Ok(collect(
+					password.len(),
+					password
+						.chars()
+						.map(|character| {
+							if is_non_ascii_space(character) {
+								' '
+							} else {
+								character
+							}
+						})
+						.nfc(),
+				))
+			}
+		}
+	}
+}
+
+/// Collects `characters` into a zeroizing buffer. When the buffer has to grow,
+/// the contents are moved to a new buffer and the old one is zeroized, so no
+/// copies of the password are left behind by reallocations.
+#[allow(clippy::integer_arithmetic)]
+fn collect(capacity: usize, characters: impl Iterator<Item = char>) -> Zeroizing<Vec<u8>> {
+	let mut buffer = Zeroizing::new(String::with_capacity(capacity));
+
+	for character in characters {
+		if buffer.capacity() - buffer.len() < character.len_utf8() {
+			let capacity = (buffer.capacity() * 2).max(buffer.len() + character.len_utf8());
+			let mut grown = Zeroizing::new(String::with_capacity(capacity));
+			grown.push_str(&buffer);
+			buffer = grown;
+		}
+
+		buffer.push(character);
+	}
+
+	Zeroizing::new(mem::take(&mut *buffer).into_bytes())
+}
+
+/// Returns `true` if `character` is a non-ASCII space, Unicode category `Zs`.
+const fn is_non_ascii_space(character: char) -> bool {
+	matches!(
+		character,
+		'\u{a0}' | '\u{1680}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+	)
+}
+
+/// Password requirements dictated by the server, see
+/// [`ServerConfig::with_policy()`](crate::ServerConfig::with_policy). The
+/// server never sees the password, so they are sent along with the
+/// [`RegistrationResponse`](crate::RegistrationResponse) and enforced by the
+/// client in
+/// [`ClientRegistration::finish()`](crate::ClientRegistration::finish).
+///
+/// Lengths are counted in characters after [`Normalization`], or in bytes if
+/// the password isn't valid UTF-8.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct PasswordPolicy {
+	/// Minimum length.
+	min_length: u16,
+	/// Maximum length.
+	max_length: u16,
+	/// Minimum [`Score`].
+	#[cfg(feature = "zxcvbn")]
+	min_score: Score,
+}
+
+impl Default for PasswordPolicy {
+	fn default() -> Self {
+		Self::NONE
+	}
+}
+
+impl PasswordPolicy {
+	/// [`PasswordPolicy`] accepting any password.
+	pub const NONE: Self = Self {
+		min_length: 0,
+		max_length: u16::MAX,
+		#[cfg(feature = "zxcvbn")]
+		min_score: Score::VeryWeak,
+	};
+
+	/// Construct a new [`PasswordPolicy`]. Lengths that aren't given aren't
+	/// restricted.
+	///
+	/// # Errors
+	/// [`Error::PasswordPolicy`] if `min_length` is higher than `max_length`.
+	pub fn new<Min: Into<Option<u16>>, Max: Into<Option<u16>>>(
+		min_length: Min,
+		max_length: Max,
+	) -> Result<Self> {
+		let min_length = min_length.into().unwrap_or(Self::NONE.min_length);
+		let max_length = max_length.into().unwrap_or(Self::NONE.max_length);
+
+		if min_length > max_length {
+			Err(Error::PasswordPolicy)
+		} else {
+			Ok(Self {
+				min_length,
+				max_length,
+				#[cfg(feature = "zxcvbn")]
+				min_score: Self::NONE.min_score,
+			})
+		}
+	}
+
+	/// Returns the minimum length.
+	#[must_use]
+	pub const fn min_length(self) -> u16 {
+		self.min_length
+	}
+
+	/// Returns the maximum length.
+	#[must_use]
+	pub const fn max_length(self) -> u16 {
+		self.max_length
+	}
+
+	/// Returns the minimum [`Score`].
+	#[cfg(feature = "zxcvbn")]
+	#[must_use]
+	pub const fn min_score(self) -> Score {
+		self.min_score
+	}
+
+	/// Returns a [`PasswordPolicy`] rejecting passwords with a lower [`Score`]
+	/// than `min_score`, see [`Strength`].
+	#[cfg(feature = "zxcvbn")]
+	#[must_use]
+	pub const fn with_min_score(mut self, min_score: Score) -> Self {
+		self.min_score = min_score;
+		self
+	}
+
+	/// Checks the normalized `password` against this [`PasswordPolicy`].
+	/// `banned` substrings are compared case-insensitively, empty ones are
+	/// ignored. With the `zxcvbn` crate feature they are also used as
+	/// user-specific words when estimating the `Strength`, passwords that
+	/// aren't valid UTF-8 aren't estimated.
+	///
+	/// # Errors
+	/// - [`Error::PasswordTooShort`] if `password` is shorter than
+	///   [`min_length()`](Self::min_length)
+	/// - [`Error::PasswordTooLong`] if `password` is longer than
+	///   [`max_length()`](Self::max_length)
+	/// - [`Error::PasswordBanned`] if `password` contains a `banned` substring
+	/// - `Error::PasswordWeak` if `password` is below `min_score()`
+	pub(crate) fn check(self, password: &[u8], banned: &[&str]) -> Result<()> {
+		let text = str::from_utf8(password).ok();
+		let length = text.map_or(password.len(), |text| text.chars().count());
+
+		if length < usize::from(self.min_length) {
+			return Err(Error::PasswordTooShort(self.min_length));
+		}
+
+		if length > usize::from(self.max_length) {
+			return Err(Error::PasswordTooLong(self.max_length));
+		}
+
+		if let Some(text) = text {
+			let text = Zeroizing::new(text.to_lowercase());
+
+			if let Some(banned) = banned
+				.iter()
+				.find(|banned| !banned.is_empty() && text.contains(&banned.to_lowercase()))
+			{
+				return Err(Error::PasswordBanned((*banned).to_owned()));
+			}
+
+			#[cfg(feature = "zxcvbn")]
+			if self.min_score > Score::VeryWeak {
+				let strength = Strength::estimate(&text, banned);
+
+				if strength.score() < self.min_score {
+					return Err(Error::PasswordWeak(strength.feedback().clone()));
+				}
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,128 @@
+//! Symmetric cryptography shared by the key management types.
+
+use chacha20poly1305::{
+	aead::{Aead, NewAead, Payload},
+	Key, XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac, NewMac};
+use opaque_ke::rand::{rngs::OsRng, RngCore};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Implements [`ConstantTimeEq`](subtle::ConstantTimeEq), [`PartialEq`] and
+/// [`Eq`] in constant-time and a [`Debug`](std::fmt::Debug) that doesn't
+/// reveal the secret, only the listed fields are shown. Without a comparison
+/// the bytes of the first field are compared.
+macro_rules! impl_secret {
+	($type:ident) => {
+		$crate::crypto::impl_secret!($type, |this, other| this.0[..].ct_eq(&other.0[..]));
+	};
+	($type:ident, |$this:ident, $other:ident| $ct_eq:expr) => {
+		$crate::crypto::impl_secret!($type, |$this, $other| $ct_eq, []);
+	};
+	($type:ident, |$this:ident, $other:ident| $ct_eq:expr, [$($field:ident),*]) => {
+		impl ::subtle::ConstantTimeEq for $type {
+			fn ct_eq(&self, other: &Self) -> ::subtle::Choice {
+				#[allow(unused_imports)]
+				use ::subtle::ConstantTimeEq as _;
+
+				let ($this, $other) = (self, other);
+				$ct_eq
+			}
+		}
+
+		impl PartialEq for $type {
+			fn eq(&self, other: &Self) -> bool {
+				::subtle::ConstantTimeEq::ct_eq(self, other).into()
+			}
+		}
+
+		impl Eq for $type {}
+
+		impl ::std::fmt::Debug for $type {
+			fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+				f.debug_struct(stringify!($type))
+					$(.field(stringify!($field), &self.$field))*
+					.finish_non_exhaustive()
+			}
+		}
+	};
+}
+
+pub(crate) use impl_secret;
+
+/// Size of a nonce used by [`seal()`] and [`open()`].
+pub(crate) const NONCE_SIZE: usize = 24;
+/// Size of the authentication tag appended by [`seal()`].
+pub(crate) const TAG_SIZE: usize = 16;
+
+/// Returns `N` random bytes.
+pub(crate) fn random<const N: usize>() -> [u8; N] {
+	let mut bytes = [0; N];
+	OsRng.fill_bytes(&mut bytes);
+
+	bytes
+}
+
+/// Derives a 32-byte key from `ikm` with HKDF-SHA256.
+pub(crate) fn derive_key(ikm: &[u8], salt: &[u8], info: &[u8]) -> Zeroizing<[u8; 32]> {
+	let mut key = Zeroizing::new([0; 32]);
+	Hkdf::<Sha256>::new(Some(salt), ikm)
+		.expand(info, key.as_mut())
+		.expect("unexpected size");
+
+	key
+}
+
+/// Computes HMAC-SHA256 over the concatenation of `data`.
+pub(crate) fn mac(key: &[u8; 32], data: &[&[u8]]) -> [u8; 32] {
+	let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("unexpected size");
+
+	for data in data {
+		mac.update(data);
+	}
+
+	mac.finalize().into_bytes().into()
+}
+
+/// Verifies a `tag` produced by [`mac()`] in constant-time.
+pub(crate) fn verify(key: &[u8; 32], data: &[&[u8]], tag: &[u8]) -> bool {
+	let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("unexpected size");
+
+	for data in data {
+		mac.update(data);
+	}
+
+	mac.verify(tag).is_ok()
+}
+
+/// Encrypts `plaintext` with `XChaCha20Poly1305` under a random nonce.
+pub(crate) fn seal(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> ([u8; NONCE_SIZE], Vec<u8>) {
+	let nonce = random();
+	let ciphertext = XChaCha20Poly1305::new(Key::from_slice(key))
+		.encrypt(XNonce::from_slice(&nonce), Payload {
+			msg: plaintext,
+			aad,
+		})
+		.expect("plaintext too long");
+
+	(nonce, ciphertext)
+}
+
+/// Decrypts `ciphertext` produced by [`seal()`]. Returns [`None`] if
+/// authentication fails.
+pub(crate) fn open(
+	key: &[u8; 32],
+	nonce: &[u8; NONCE_SIZE],
+	aad: &[u8],
+	ciphertext: &[u8],
+) -> Option<Zeroizing<Vec<u8>>> {
+	XChaCha20Poly1305::new(Key::from_slice(key))
+		.decrypt(XNonce::from_slice(nonce), Payload {
+			msg: ciphertext,
+			aad,
+		})
+		.ok()
+		.map(Zeroizing::new)
+}
@@ -0,0 +1,228 @@
+//! Account deletion requiring knowledge of the password, see
+//! [`ServerDeletion`].
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	crypto, ClientConfig, ClientFile, ClientLogin, Config, Error, LoginFinalization, LoginRequest,
+	LoginResponse, Result, ServerConfig, ServerFile, ServerLogin, SessionKey,
+};
+
+/// Domain separation of the transcript context and HKDF info used to derive
+/// the key authenticating the deletion intent.
+const INFO: &[u8] = b"custodian-password deletion";
+
+/// Send this back to the client. See [`ClientDeletion::finish()`].
+#[must_use = "Does nothing if not sent to the client"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct DeletionResponse {
+	/// Random nonce of the server.
+	nonce: [u8; 32],
+	/// [`LoginResponse`] bound to the deletion.
+	response: LoginResponse,
+}
+
+impl DeletionResponse {
+	/// Returns [`Config`] used to create this [`DeletionResponse`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.response.config
+	}
+}
+
+/// Send this back to the server. See [`ServerDeletion::finish()`].
+#[must_use = "Does nothing if not sent to the server"]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct DeletionFinalization {
+	/// [`LoginFinalization`] bound to the deletion.
+	finalization: LoginFinalization,
+	/// Deletion intent authenticated with the [`SessionKey`].
+	intent: [u8; 32],
+}
+
+impl DeletionFinalization {
+	/// Returns [`Config`] used to create this [`DeletionFinalization`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.finalization.config
+	}
+}
+
+/// Holds the state of an account deletion on the client. See
+/// [`delete()`](Self::delete).
+#[must_use = "Does nothing if not `finish`ed"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClientDeletion {
+	/// Underlying login process.
+	login: ClientLogin,
+}
+
+impl ClientDeletion {
+	/// Returns the [`ClientConfig`] associated with this [`ClientDeletion`].
+	#[must_use]
+	pub const fn config(&self) -> ClientConfig {
+		self.login.config()
+	}
+
+	/// Starts the account deletion. The returned [`LoginRequest`] has to be
+	/// send to the server. See [`ServerDeletion::delete()`].
+	///
+	/// # Errors
+	/// See [`ClientLogin::login()`].
+	pub fn delete<P: AsRef<[u8]>>(
+		config: ClientConfig,
+		file: Option<ClientFile>,
+		password: P,
+	) -> Result<(Self, LoginRequest)> {
+		let (login, request) = ClientLogin::login(config, file, password)?;
+
+		Ok((Self { login }, request))
+	}
+
+	/// Finishes the account deletion on the client. The returned
+	/// [`DeletionFinalization`] has to be send back to the server. See
+	/// [`ServerDeletion::finish()`].
+	///
+	/// # Errors
+	/// See [`ClientLogin::finish()`].
+	pub fn finish(self, response: DeletionResponse) -> Result<DeletionFinalization> {
+		let (_, finalization, _, session_key) = self
+			.login
+			.finish_with_context(response.response, &context(&response.nonce))?;
+
+		Ok(DeletionFinalization {
+			finalization,
+			intent: intent(&session_key, &response.nonce),
+		})
+	}
+}
+
+/// Holds the state of an account deletion on the server. See
+/// [`delete()`](Self::delete).
+#[must_use = "Does nothing if not `finish`ed"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ServerDeletion {
+	/// Underlying login process.
+	login: ServerLogin,
+	/// [`ServerFile`] to delete.
+	file: ServerFile,
+	/// Random nonce of the server.
+	nonce: [u8; 32],
+}
+
+impl ServerDeletion {
+	/// Returns the [`Config`] associated with this [`ServerDeletion`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.login.config()
+	}
+
+	/// Starts the deletion of the account of `file`. The returned
+	/// [`DeletionResponse`] has to be send back to the client. See
+	/// [`ClientDeletion::finish()`].
+	///
+	/// # Errors
+	/// See [`ServerLogin::login()`].
+	pub fn delete(
+		config: &ServerConfig,
+		file: ServerFile,
+		request: LoginRequest,
+	) -> Result<(Self, DeletionResponse)> {
+		let nonce = crypto::random();
+		let (login, response) =
+			ServerLogin::login_with_context(config, Some(file.clone()), request, &context(&nonce))?;
+
+		Ok((Self { login, file, nonce }, DeletionResponse {
+			nonce,
+			response,
+		}))
+	}
+
+	/// Finishes the account deletion. The returned [`DeletionAuthorization`]
+	/// authorizes dropping the [`ServerFile`].
+	///
+	/// # Errors
+	/// - [`Error::DeletionIntent`] if the client didn't authenticate the
+	///   deletion
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn finish(self, finalization: DeletionFinalization) -> Result<DeletionAuthorization> {
+		let (session_key, _) = self.login.finish(finalization.finalization)?;
+
+		if !crypto::verify(
+			&crypto::derive_key(session_key.as_slice(), &[], INFO),
+			&[&self.nonce],
+			&finalization.intent,
+		) {
+			return Err(Error::DeletionIntent);
+		}
+
+		Ok(DeletionAuthorization { file: self.file })
+	}
+}
+
+/// Authorization to drop a [`ServerFile`], returned by
+/// [`ServerDeletion::finish()`] after the client proved knowledge of the
+/// password.
+#[must_use = "Use this to drop the `ServerFile`"]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeletionAuthorization {
+	/// [`ServerFile`] to delete.
+	file: ServerFile,
+}
+
+impl DeletionAuthorization {
+	/// Returns the [`ServerFile`] that is authorized to be dropped.
+	pub const fn file(&self) -> &ServerFile {
+		&self.file
+	}
+}
+
+/// Context bound into the login transcript.
+fn context(nonce: &[u8; 32]) -> Vec<u8> {
+	[INFO, nonce].concat()
+}
+
+/// Authenticates the deletion intent with the [`SessionKey`].
+fn intent(session_key: &SessionKey, nonce: &[u8; 32]) -> [u8; 32] {
+	crypto::mac(&crypto::derive_key(session_key.as_slice(), &[], INFO), &[
+		nonce,
+	])
+}
+
+#[test]
+fn deletion() -> anyhow::Result<()> {
+	use crate::{ClientRegistration, ServerRegistration};
+
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	// deletion process
+	let (client, request) = ClientDeletion::delete(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerDeletion::delete(&server_config, server_file.clone(), request)?;
+	let response: DeletionResponse = bincode::deserialize(&bincode::serialize(&response)?)?;
+	let finalization = client.finish(response)?;
+	let finalization: DeletionFinalization =
+		bincode::deserialize(&bincode::serialize(&finalization)?)?;
+	let authorization = server.finish(finalization)?;
+	assert_eq!(authorization.file(), &server_file);
+
+	// wrong password
+	let (client, request) = ClientDeletion::delete(client_config, Some(client_file), b"wrong")?;
+	let (_, response) = ServerDeletion::delete(&server_config, server_file.clone(), request)?;
+	assert_eq!(client.finish(response), Err(Error::Credentials));
+
+	// tampered intent
+	let (client, request) = ClientDeletion::delete(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerDeletion::delete(&server_config, server_file, request)?;
+	let mut finalization = client.finish(response)?;
+	finalization.intent[0] ^= 1;
+	assert_eq!(server.finish(finalization), Err(Error::DeletionIntent));
+
+	Ok(())
+}
@@ -6,6 +6,9 @@
 pub use opaque_ke::errors::{InternalError, ProtocolError};
 use thiserror::Error;
 
+#[cfg(feature = "zxcvbn")]
+use crate::Feedback;
+
 /// [`Result`](std::result::Result) for this crate.
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -35,4 +38,200 @@ pub enum Error {
 	/// [`ServerConfig`](crate::ServerConfig).
 	#[error("Server file was not created with the same server configuration")]
 	ServerFile,
+	/// [`KeySlot`](crate::KeySlot) is empty.
+	#[error("Key slot is empty")]
+	KeySlot,
+	/// [`WrappedKey`](crate::WrappedKey) couldn't be unlocked with the given
+	/// key.
+	#[error("Wrapped key couldn't be unlocked")]
+	WrappedKey,
+	/// [`RecoveryCode`](crate::RecoveryCode) couldn't be parsed.
+	#[error("Recovery code is malformed")]
+	RecoveryCode,
+	/// Decrypting data encrypted to a
+	/// [`RecipientPublicKey`](crate::RecipientPublicKey) failed.
+	#[error("Decryption failed")]
+	Decryption,
+	/// [`RecipientPublicKey`](crate::RecipientPublicKey) is invalid.
+	#[error("Recipient public key is invalid")]
+	RecipientPublicKey,
+	/// Threshold is `0` or exceeds the number of trustees.
+	#[error("Threshold is invalid")]
+	Threshold,
+	/// Not enough or inconsistent [`Share`](crate::Share)s.
+	#[error("Not enough or inconsistent shares")]
+	Shares,
+	/// [`SecureChannel`](crate::SecureChannel) message couldn't be
+	/// authenticated.
+	#[error("Message couldn't be authenticated")]
+	Channel,
+	/// [`SecureChannel`](crate::SecureChannel) message was replayed, reordered
+	/// or previous messages were dropped.
+	#[error("Message out of sequence")]
+	Sequence,
+	/// [`SecureChannel`](crate::SecureChannel) has no epochs left.
+	#[error("Channel is exhausted")]
+	ChannelExhausted,
+	/// [`ResumptionTicket`](crate::ResumptionTicket) wasn't issued by this
+	/// [`ServerConfig`](crate::ServerConfig) or the client couldn't prove
+	/// possession.
+	#[error("Resumption ticket is invalid")]
+	Ticket,
+	/// [`ResumptionTicket`](crate::ResumptionTicket) expired.
+	#[error("Resumption ticket expired")]
+	TicketExpired,
+	/// [`ResumptionTicket`](crate::ResumptionTicket) was already used or
+	/// revoked.
+	#[error("Resumption ticket was already used or revoked")]
+	TicketUsed,
+	/// [`ReauthProof`](crate::ReauthProof) wasn't issued by this
+	/// [`ServerConfig`](crate::ServerConfig) or is for a different subject or
+	/// action.
+	#[error("Re-authentication proof is invalid")]
+	ReauthProof,
+	/// [`ReauthProof`](crate::ReauthProof) is older than allowed.
+	#[error("Re-authentication proof expired")]
+	ReauthExpired,
+	/// TOTP code is invalid.
+	#[error("TOTP code is invalid")]
+	Totp,
+	/// Time step of the TOTP code was already used.
+	#[error("TOTP code was already used")]
+	TotpReplay,
+	/// [`EncryptedTotp`](crate::EncryptedTotp) couldn't be decrypted with the
+	/// given [`ExportKey`](crate::ExportKey).
+	#[error("TOTP secret couldn't be decrypted")]
+	TotpSecret,
+	/// [`ServerTotp`](crate::ServerTotp) was not created with the same
+	/// [`ServerConfig`](crate::ServerConfig).
+	#[error("Server TOTP was not created with the same server configuration")]
+	ServerTotp,
+	/// Registration requires an [`Invitation`](crate::Invitation).
+	#[error("Invitation is missing")]
+	InvitationMissing,
+	/// [`Invitation`](crate::Invitation) wasn't issued by this
+	/// [`ServerConfig`](crate::ServerConfig).
+	#[error("Invitation is invalid")]
+	Invitation,
+	/// [`Invitation`](crate::Invitation) expired.
+	#[error("Invitation expired")]
+	InvitationExpired,
+	/// [`Invitation`](crate::Invitation) was already used or revoked.
+	#[error("Invitation was already used or revoked")]
+	InvitationUsed,
+	/// [`ResetToken`](crate::ResetToken) wasn't issued by this
+	/// [`ServerConfig`](crate::ServerConfig) or for a different identifier.
+	#[error("Reset token is invalid")]
+	ResetToken,
+	/// [`ResetToken`](crate::ResetToken) expired.
+	#[error("Reset token expired")]
+	ResetTokenExpired,
+	/// [`ResetToken`](crate::ResetToken) was already used or revoked.
+	#[error("Reset token was already used or revoked")]
+	ResetTokenUsed,
+	/// Client didn't authenticate the deletion intent.
+	#[error("Deletion intent couldn't be authenticated")]
+	DeletionIntent,
+	/// [`Difficulty`](crate::Difficulty) is higher than
+	/// [`Difficulty::MAX`](crate::Difficulty::MAX).
+	#[error("Difficulty is too high")]
+	Difficulty,
+	/// [`Challenge`](crate::Challenge) wasn't issued by this
+	/// [`ServerConfig`](crate::ServerConfig).
+	#[error("Challenge is invalid")]
+	Challenge,
+	/// [`Challenge`](crate::Challenge) expired.
+	#[error("Challenge expired")]
+	ChallengeExpired,
+	/// [`Challenge`](crate::Challenge) was already used or revoked.
+	#[error("Challenge was already used or revoked")]
+	ChallengeUsed,
+	/// [`Solution`](crate::Solution) doesn't solve the
+	/// [`Challenge`](crate::Challenge).
+	#[error("Challenge wasn't solved")]
+	Solution,
+	/// Password isn't valid UTF-8 or is rejected by the
+	/// [`Normalization`](crate::Normalization).
+	#[error("Password is invalid")]
+	Password,
+	/// [`PasswordPolicy`](crate::PasswordPolicy) has a higher minimum than
+	/// maximum length.
+	#[error("Password policy is invalid")]
+	PasswordPolicy,
+	/// Password is shorter than the minimum length of the
+	/// [`PasswordPolicy`](crate::PasswordPolicy).
+	#[error("Password must be at least {0} characters long")]
+	PasswordTooShort(u16),
+	/// Password is longer than the maximum length of the
+	/// [`PasswordPolicy`](crate::PasswordPolicy).
+	#[error("Password must be at most {0} characters long")]
+	PasswordTooLong(u16),
+	/// Password contains a banned substring.
+	#[error("Password must not contain \"{0}\"")]
+	PasswordBanned(String),
+	/// Password is below the minimum [`Score`](crate::Score) of the
+	/// [`PasswordPolicy`](crate::PasswordPolicy).
+	#[cfg(feature = "zxcvbn")]
+	#[error("Password is too easy to guess")]
+	PasswordWeak(Feedback),
+	/// Password appeared in a data breach, see
+	/// [`BreachFilter`](crate::BreachFilter) and
+	/// [`BreachCorpus`](crate::BreachCorpus).
+	#[error("Password appeared in a data breach")]
+	PasswordBreached,
+	/// [`BreachFilter`](crate::BreachFilter) parameters or bytes are invalid.
+	#[error("Breach filter is invalid")]
+	BreachFilter,
+	/// [`BreachCorpus`](crate::BreachCorpus) couldn't be read or is
+	/// malformed.
+	#[error("Breach corpus couldn't be read")]
+	BreachCorpus,
+	/// [`BreachRequest`](crate::BreachRequest) contains an invalid blinded
+	/// password.
+	#[error("Breach request is invalid")]
+	BreachRequest,
+	/// [`BreachResponse`](crate::BreachResponse) contains an invalid
+	/// evaluated password.
+	#[error("Breach response is invalid")]
+	BreachResponse,
+	/// The OPRF of [`BreachServer`](crate::BreachServer) or
+	/// [`HardeningServer`](crate::HardeningServer) failed to hash or blind a
+	/// password.
+	#[error("OPRF failed")]
+	Oprf,
+	/// [`HardeningRequest`](crate::HardeningRequest) contains an invalid
+	/// blinded password.
+	#[error("Hardening request is invalid")]
+	HardeningRequest,
+	/// [`HardeningResponse`](crate::HardeningResponse) or
+	/// [`HardenedPassword`](crate::HardenedPassword) contains an invalid point.
+	#[error("Hardening response is invalid")]
+	HardeningResponse,
+	/// [`UpdateToken`](crate::UpdateToken) doesn't update from the epoch of
+	/// the [`HardenedPassword`](crate::HardenedPassword).
+	#[error("Update token doesn't match the epoch")]
+	UpdateToken,
+	/// [`HardeningServer`](crate::HardeningServer) can't be rotated past the
+	/// last epoch.
+	#[error("Epoch exhausted")]
+	Epoch,
+	/// [`LegacyHash`](crate::LegacyHash) is malformed or uses an unsupported
+	/// algorithm.
+	#[error("Legacy password hash is invalid")]
+	LegacyHash,
+	/// [`Token`](crate::Token) is malformed or wasn't issued by the
+	/// [`ServerConfig`](crate::ServerConfig) of the
+	/// [`TokenVerifier`](crate::TokenVerifier).
+	#[cfg(feature = "token")]
+	#[error("Token is invalid")]
+	Token,
+	/// [`Token`](crate::Token) expired.
+	#[cfg(feature = "token")]
+	#[error("Token expired")]
+	TokenExpired,
+	/// [`TokenVerifier`](crate::TokenVerifier) couldn't be created from
+	/// bytes.
+	#[cfg(feature = "token")]
+	#[error("Token verifier is invalid")]
+	TokenVerifier,
 }
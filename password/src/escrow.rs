@@ -0,0 +1,151 @@
+//! Escrow of [`ExportKey`]-derived keys to an administrator, see
+//! [`ClientConfig::with_escrow()`](crate::ClientConfig::with_escrow).
+
+use std::{convert::TryInto, fmt::Debug};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{
+	crypto,
+	recipient::{RecipientPublicKey, RecipientSecretKey, Sealed},
+	DataKey, Error, ExportKey, KeySlot, Result, WrappedKey,
+};
+
+/// HKDF info used to derive the [`EscrowKey`] from an [`ExportKey`].
+const INFO: &[u8] = b"custodian-password escrow";
+
+/// Key derived from an [`ExportKey`] that is escrowed to an administrator.
+/// Can only unlock [`KeySlot::Escrow`], see
+/// [`WrappedKey::unlock_escrow()`].
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct EscrowKey([u8; 32]);
+
+impl EscrowKey {
+	/// Derives the [`EscrowKey`] from an [`ExportKey`].
+	fn new(export_key: &ExportKey) -> Self {
+		Self(*crypto::derive_key(export_key.as_slice(), &[], INFO))
+	}
+}
+
+crypto::impl_secret!(EscrowKey);
+
+/// [`EscrowKey`] encrypted to an administrators [`RecipientPublicKey`].
+///
+/// Sent to the server alongside
+/// [`RegistrationFinalization`](crate::RegistrationFinalization) and
+/// [`LoginFinalization`](crate::LoginFinalization) if escrow is enabled.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Escrow {
+	/// [`RecipientPublicKey`] of the administrator.
+	recipient: RecipientPublicKey,
+	/// Encrypted [`EscrowKey`].
+	key: Sealed,
+}
+
+impl Escrow {
+	/// Encrypts the [`EscrowKey`] derived from `export_key` to `recipient`.
+	pub(crate) fn new(recipient: RecipientPublicKey, export_key: &ExportKey) -> Result<Self> {
+		Ok(Self {
+			recipient,
+			key: recipient.encrypt(INFO, &EscrowKey::new(export_key).0)?,
+		})
+	}
+
+	/// Returns the [`RecipientPublicKey`] of the administrator this [`Escrow`]
+	/// was encrypted to.
+	#[must_use]
+	pub const fn recipient(&self) -> RecipientPublicKey {
+		self.recipient
+	}
+
+	/// Decrypts the [`EscrowKey`] with the administrators
+	/// [`RecipientSecretKey`].
+	///
+	/// # Errors
+	/// [`Error::Decryption`] if `secret_key` doesn't belong to the
+	/// administrator.
+	pub fn decrypt(&self, secret_key: &RecipientSecretKey) -> Result<EscrowKey> {
+		Ok(EscrowKey(
+			secret_key
+				.decrypt(INFO, &self.key)?
+				.as_slice()
+				.try_into()
+				.map_err(|_| Error::Decryption)?,
+		))
+	}
+}
+
+impl WrappedKey {
+	/// Wraps `data_key` in [`KeySlot::Escrow`] with the [`EscrowKey`] derived
+	/// from `export_key`, replacing the previous content.
+	pub fn add_escrow_slot(&mut self, data_key: &DataKey, export_key: &ExportKey) {
+		self.add_slot_with(KeySlot::Escrow, data_key, &EscrowKey::new(export_key).0);
+	}
+
+	/// Decrypts the [`DataKey`] stored in [`KeySlot::Escrow`] with an
+	/// [`EscrowKey`].
+	///
+	/// # Errors
+	/// - [`Error::KeySlot`] if [`KeySlot::Escrow`] is empty
+	/// - [`Error::WrappedKey`] if `escrow_key` doesn't belong to this
+	///   [`WrappedKey`]
+	pub fn unlock_escrow(&self, escrow_key: &EscrowKey) -> Result<DataKey> {
+		self.unlock_with(KeySlot::Escrow, &escrow_key.0)
+	}
+}
+
+#[test]
+fn escrow() -> anyhow::Result<()> {
+	use crate::{
+		ClientConfig, ClientLogin, ClientRegistration, ServerConfig, ServerLogin,
+		ServerRegistration,
+	};
+
+	const PASSWORD: &[u8] = b"password";
+	let administrator = RecipientSecretKey::generate();
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+	let escrow_config = client_config.with_escrow(administrator.public_key());
+	assert_eq!(client_config.escrow(), None);
+	assert_eq!(escrow_config.escrow(), Some(administrator.public_key()));
+
+	// registration process
+	let (client, request) = ClientRegistration::register(escrow_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, export_key) = client.finish(response)?;
+	let escrow = finalization.escrow().cloned().expect("no escrow");
+	assert_eq!(escrow.recipient(), administrator.public_key());
+	let server_file = server.finish(finalization)?;
+
+	let (mut wrapped_key, data_key) = WrappedKey::new(&export_key);
+	wrapped_key.add_escrow_slot(&data_key, &export_key);
+
+	// the administrator recovers the data key
+	let escrow: Escrow = bincode::deserialize(&bincode::serialize(&escrow)?)?;
+	assert_eq!(
+		escrow.decrypt(&RecipientSecretKey::generate()),
+		Err(Error::Decryption)
+	);
+	let escrow_key = escrow.decrypt(&administrator)?;
+	assert_eq!(wrapped_key.unlock_escrow(&escrow_key)?, data_key);
+
+	// login process
+	let (client, request) = ClientLogin::login(escrow_config, Some(client_file), PASSWORD)?;
+	let (server, response) =
+		ServerLogin::login(&server_config, Some(server_file.clone()), request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	let escrow = finalization.escrow().expect("no escrow");
+	assert_eq!(escrow.decrypt(&administrator)?, escrow_key);
+	server.finish(finalization)?;
+
+	// without escrow
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	assert_eq!(finalization.escrow(), None);
+	server.finish(finalization)?;
+
+	Ok(())
+}
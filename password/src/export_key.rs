@@ -6,9 +6,11 @@ use arrayvec::ArrayVec;
 use serde::{Deserialize, Serialize};
 use zeroize::Zeroize;
 
+use crate::crypto;
+
 /// Secret key derived from the users password on the client. The server has no
 /// access to it! Can be used to encrypt data and store it safely at the server.
-#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Zeroize)]
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
 #[zeroize(drop)]
 pub struct ExportKey(ArrayVec<u8, 64>);
 
@@ -25,6 +27,8 @@ impl ExportKey {
 	}
 }
 
+crypto::impl_secret!(ExportKey);
+
 impl AsRef<ArrayVec<u8, 64>> for ExportKey {
 	fn as_ref(&self) -> &ArrayVec<u8, 64> {
 		self.as_bytes()
@@ -0,0 +1,349 @@
+//! Pythia-style password hardening oracle for legacy password storage, see
+//! [`HardeningServer`].
+
+use std::{
+	convert::TryFrom,
+	fmt::{self, Debug, Formatter},
+};
+
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+	crypto,
+	oprf::{self, ELEMENT_SIZE},
+	Error, Result, ServerConfig,
+};
+
+/// HKDF info used to derive the OPRF key of epoch 0 and domain separation of
+/// the OPRF.
+const INFO: &[u8; 28] = b"custodian-password hardening";
+
+/// Oracle hardening passwords of services that can't use OPAQUE yet.
+///
+/// The service blinds a password with [`HardeningClient::blind()`], the
+/// oracle evaluates an OPRF with the key of the current epoch, and the
+/// service stores the unblinded
+/// [`HardenedPassword`] instead of, or as input to, its existing password
+/// hash. The oracle never learns the password and a stolen password database
+/// can't be attacked offline without the oracle key.
+///
+/// The key of epoch 0 is derived from the [`ServerConfig`], like the one of a
+/// [`BreachServer`](crate::BreachServer), and can be recreated with
+/// [`new()`](Self::new). The key is rotated by moving to a new epoch with a
+/// fresh random key, stored [`HardenedPassword`]s are updated with an
+/// [`UpdateToken`] without knowing the passwords.
+///
+/// # Persistence
+/// After [`rotate()`](Self::rotate) the serialized [`HardeningServer`] is the
+/// only copy of the key. The oracle has to persist it, e.g. with [`serde`],
+/// before handing out the [`UpdateToken`], losing it makes all
+/// [`HardenedPassword`]s of that epoch unverifiable.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HardeningServer {
+	/// OPRF key of the current epoch.
+	key: oprf::Key,
+	/// Current epoch.
+	epoch: u32,
+}
+
+impl Debug for HardeningServer {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("HardeningServer")
+			.field("epoch", &self.epoch)
+			.finish_non_exhaustive()
+	}
+}
+
+impl HardeningServer {
+	/// Creates a [`HardeningServer`] at epoch 0 with an OPRF key derived from
+	/// `config`.
+	#[must_use]
+	pub fn new(config: &ServerConfig) -> Self {
+		let key = config.derive_key(INFO);
+
+		Self {
+			key: oprf::Key::derive(&key[..]),
+			epoch: 0,
+		}
+	}
+
+	/// Returns the current epoch.
+	#[must_use]
+	pub const fn epoch(&self) -> u32 {
+		self.epoch
+	}
+
+	/// Evaluates the OPRF on the blinded password of `request`. The returned
+	/// [`HardeningResponse`] has to be sent back to the service. See
+	/// [`HardeningClient::finish()`].
+	///
+	/// # Errors
+	/// [`Error::HardeningRequest`] if the blinded password is invalid.
+	pub fn evaluate(&self, request: &HardeningRequest) -> Result<HardeningResponse> {
+		Ok(HardeningResponse {
+			evaluated: self
+				.key
+				.evaluate(&request.0, INFO)
+				.ok_or(Error::HardeningRequest)?,
+			epoch: self.epoch,
+		})
+	}
+
+	/// Rotates the key by moving to the next epoch with a fresh random key.
+	/// The returned [`UpdateToken`] updates [`HardenedPassword`]s of the
+	/// current epoch, see [`HardenedPassword::update()`].
+	///
+	/// The returned [`HardeningServer`] holds the only copy of the new key,
+	/// persist it before using the [`UpdateToken`].
+	///
+	/// # Errors
+	/// [`Error::Epoch`] if the epoch is exhausted.
+	pub fn rotate(&self) -> Result<(Self, UpdateToken)> {
+		let next = Self {
+			key: oprf::Key::random(),
+			epoch: self.epoch.checked_add(1).ok_or(Error::Epoch)?,
+		};
+		let token = UpdateToken {
+			from: self.epoch,
+			to: next.epoch,
+			delta: *next.key.delta(&self.key),
+		};
+
+		Ok((next, token))
+	}
+}
+
+/// Holds the state of a hardening process. See [`blind()`](Self::blind).
+#[must_use = "Use `finish()` to complete the hardening process"]
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct HardeningClient {
+	/// OPRF state of the blinded password.
+	client: oprf::Client,
+}
+
+impl Debug for HardeningClient {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("HardeningClient").finish_non_exhaustive()
+	}
+}
+
+impl HardeningClient {
+	/// Starts hardening `password`. The returned [`HardeningRequest`] has to
+	/// be sent to the oracle. See [`HardeningServer::evaluate()`].
+	///
+	/// `salt` separates users with the same password, e.g. the user id or the
+	/// salt of the existing password hash.
+	///
+	/// # Errors
+	/// [`Error::Oprf`] if the OPRF failed.
+	pub fn blind<S: AsRef<[u8]>, P: AsRef<[u8]>>(
+		salt: S,
+		password: P,
+	) -> Result<(Self, HardeningRequest)> {
+		let salt = salt.as_ref();
+		let length = u64::try_from(salt.len()).map_err(|_| Error::Oprf)?;
+		let input = Zeroizing::new([&length.to_be_bytes(), salt, password.as_ref()].concat());
+		let (client, blinded) = oprf::Client::blind(&input).ok_or(Error::Oprf)?;
+
+		Ok((Self { client }, HardeningRequest(blinded)))
+	}
+
+	/// Finishes the hardening process. The returned [`HardenedPassword`] can
+	/// be stored or compared to a stored one.
+	///
+	/// # Errors
+	/// [`Error::HardeningResponse`] if the evaluated password is invalid.
+	pub fn finish(self, response: HardeningResponse) -> Result<HardenedPassword> {
+		Ok(HardenedPassword {
+			point: self
+				.client
+				.unblind(&response.evaluated)
+				.ok_or(Error::HardeningResponse)?,
+			epoch: response.epoch,
+		})
+	}
+}
+
+/// Blinded password sent by the service. See [`HardeningServer::evaluate()`].
+#[must_use = "Does nothing if not sent to the oracle"]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct HardeningRequest([u8; ELEMENT_SIZE]);
+
+/// Evaluated password sent by the oracle. See [`HardeningClient::finish()`].
+#[must_use = "Does nothing if not sent to the service"]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct HardeningResponse {
+	/// Evaluated blinded password.
+	evaluated: [u8; ELEMENT_SIZE],
+	/// Epoch of the key used.
+	epoch: u32,
+}
+
+/// Password hardened by a [`HardeningServer`].
+///
+/// Store it directly to be able to [`update()`](Self::update) it when the
+/// oracle key is rotated. Feeding [`as_bytes()`](Self::as_bytes) into an
+/// existing password hash, e.g. bcrypt, instead requires re-hardening on the
+/// next login after a rotation.
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct HardenedPassword {
+	/// OPRF output.
+	point: [u8; ELEMENT_SIZE],
+	/// Epoch of the key used.
+	epoch: u32,
+}
+
+crypto::impl_secret!(
+	HardenedPassword,
+	|this, other| this.point.ct_eq(&other.point) & this.epoch.ct_eq(&other.epoch),
+	[epoch]
+);
+
+impl HardenedPassword {
+	/// Returns the epoch of the key this [`HardenedPassword`] was hardened
+	/// with.
+	#[must_use]
+	pub const fn epoch(&self) -> u32 {
+		self.epoch
+	}
+
+	/// Returns the bytes of this [`HardenedPassword`].
+	#[must_use]
+	pub const fn as_bytes(&self) -> &[u8; ELEMENT_SIZE] {
+		&self.point
+	}
+
+	/// Updates this [`HardenedPassword`] to the epoch of `token`, as if it was
+	/// hardened with the rotated key.
+	///
+	/// # Errors
+	/// - [`Error::UpdateToken`] if `token` doesn't update from the epoch of
+	///   this [`HardenedPassword`]
+	/// - [`Error::HardeningResponse`] if this [`HardenedPassword`] is invalid
+	pub fn update(&self, token: &UpdateToken) -> Result<Self> {
+		if token.from != self.epoch {
+			return Err(Error::UpdateToken);
+		}
+
+		Ok(Self {
+			point: oprf::update(&self.point, &token.delta).ok_or(Error::HardeningResponse)?,
+			epoch: token.to,
+		})
+	}
+}
+
+/// Updates [`HardenedPassword`]s after the key of a [`HardeningServer`] was
+/// rotated. See [`HardeningServer::rotate()`].
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct UpdateToken {
+	/// Epoch updated from.
+	from: u32,
+	/// Epoch updated to.
+	to: u32,
+	/// Ratio between the new and the old key.
+	delta: [u8; 32],
+}
+
+impl Debug for UpdateToken {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("UpdateToken")
+			.field("from", &self.from)
+			.field("to", &self.to)
+			.finish_non_exhaustive()
+	}
+}
+
+impl UpdateToken {
+	/// Returns the epoch this [`UpdateToken`] updates from.
+	#[must_use]
+	pub const fn from(&self) -> u32 {
+		self.from
+	}
+
+	/// Returns the epoch this [`UpdateToken`] updates to.
+	#[must_use]
+	pub const fn to(&self) -> u32 {
+		self.to
+	}
+}
+
+#[test]
+fn hardening() -> anyhow::Result<()> {
+	let config = ServerConfig::default();
+	let server = HardeningServer::new(&config);
+	let server: HardeningServer = bincode::deserialize(&bincode::serialize(&server)?)?;
+
+	let harden = |server: &HardeningServer, salt: &str, password: &str| -> anyhow::Result<_> {
+		let (client, request) = HardeningClient::blind(salt, password)?;
+		let request: HardeningRequest = bincode::deserialize(&bincode::serialize(&request)?)?;
+		let response = server.evaluate(&request)?;
+		let response: HardeningResponse = bincode::deserialize(&bincode::serialize(&response)?)?;
+		Ok(client.finish(response)?)
+	};
+
+	let hardened = harden(&server, "alice", "password")?;
+	let stored: HardenedPassword = bincode::deserialize(&bincode::serialize(&hardened)?)?;
+	assert_eq!(stored.epoch(), 0);
+
+	// deterministic despite blinding
+	assert_eq!(harden(&server, "alice", "password")?, stored);
+	assert_ne!(harden(&server, "alice", "wrong")?, stored);
+	assert_ne!(harden(&server, "bob", "password")?, stored);
+
+	// epoch 0 is derived from the config
+	assert_eq!(
+		harden(&HardeningServer::new(&config), "alice", "password")?,
+		stored
+	);
+	assert_ne!(
+		harden(
+			&HardeningServer::new(&ServerConfig::default()),
+			"alice",
+			"password"
+		)?,
+		stored
+	);
+
+	// rotation
+	let (rotated, token) = server.rotate()?;
+	let token: UpdateToken = bincode::deserialize(&bincode::serialize(&token)?)?;
+	assert_eq!((token.from(), token.to(), rotated.epoch()), (0, 1, 1));
+	// fresh random key per epoch
+	let (other, _) = server.rotate()?;
+	assert_ne!(other.key, rotated.key);
+
+	let updated = stored.update(&token)?;
+	assert_eq!(updated.epoch(), 1);
+	assert_eq!(harden(&rotated, "alice", "password")?, updated);
+	assert_ne!(updated, stored);
+	assert_eq!(updated.update(&token).map(|_| ()), Err(Error::UpdateToken));
+	let last = HardeningServer {
+		epoch: u32::MAX,
+		..HardeningServer::new(&config)
+	};
+	assert_eq!(last.rotate().map(|_| ()), Err(Error::Epoch));
+
+	// invalid points
+	assert_eq!(
+		server.evaluate(&HardeningRequest([0xff; 32])),
+		Err(Error::HardeningRequest)
+	);
+	let (client, _) = HardeningClient::blind("alice", "password")?;
+	assert_eq!(
+		client
+			.finish(HardeningResponse {
+				evaluated: [0xff; 32],
+				epoch: 0,
+			})
+			.map(|_| ()),
+		Err(Error::HardeningResponse)
+	);
+
+	assert_eq!(format!("{:?}", stored), "HardenedPassword { epoch: 0, .. }");
+
+	Ok(())
+}
@@ -0,0 +1,196 @@
+//! Invitation-gated registration, see [`Invitation`].
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ledger::{IdentifierToken, Rejection, TokenId},
+	Error, RegistrationRequest, RegistrationResponse, Result, ServerConfig, ServerRegistration,
+	TokenLedger,
+};
+
+/// HKDF info used to derive the key authenticating [`Invitation`]s.
+const INFO: &[u8] = b"custodian-password invitation";
+
+/// Invitation to register, issued by the server and bound to an identifier,
+/// e.g. an email address. See [`ServerRegistration::register_invited()`] and
+/// [`ServerConfig::with_invitation_required()`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Invitation(IdentifierToken);
+
+impl Invitation {
+	/// Issues a new [`Invitation`] for `identifier`, valid for `validity`.
+	#[must_use]
+	pub fn issue(config: &ServerConfig, identifier: &str, validity: Duration) -> Self {
+		Self(IdentifierToken::issue(config, INFO, identifier, validity))
+	}
+
+	/// Returns the [`TokenId`] of this [`Invitation`], use it to
+	/// [revoke](TokenLedger::revoke) it.
+	#[must_use]
+	pub const fn id(&self) -> TokenId {
+		self.0.id()
+	}
+
+	/// Returns the identifier this [`Invitation`] was issued to.
+	#[must_use]
+	pub fn identifier(&self) -> &str {
+		self.0.identifier()
+	}
+
+	/// Returns the expiry of this [`Invitation`].
+	#[must_use]
+	pub fn expires(&self) -> SystemTime {
+		self.0.expires()
+	}
+}
+
+impl ServerRegistration {
+	/// Starts the registration process like [`register()`](Self::register),
+	/// but requires a valid [`Invitation`]. The [`Invitation`] is marked as
+	/// used in `ledger`, even if the registration isn't finished.
+	///
+	/// The client should be registered under
+	/// [`Invitation::identifier()`].
+	///
+	/// # Errors
+	/// - [`Error::InvitationMissing`] if no [`Invitation`] was passed
+	/// - [`Error::Invitation`] if the [`Invitation`] wasn't issued by this
+	///   [`ServerConfig`]
+	/// - [`Error::InvitationExpired`] if the [`Invitation`] expired
+	/// - [`Error::InvitationUsed`] if the [`Invitation`] was already used or
+	///   revoked
+	/// - [`Error::Config`] if [`ServerConfig`] and [`RegistrationRequest`] were
+	///   not created with the same [`Config`](crate::Config)
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn register_invited(
+		config: &ServerConfig,
+		request: RegistrationRequest,
+		invitation: Option<&Invitation>,
+		ledger: &mut TokenLedger,
+	) -> Result<(Self, RegistrationResponse)> {
+		let invitation = invitation.ok_or(Error::InvitationMissing)?;
+
+		invitation
+			.0
+			.check(config, INFO, ledger)
+			.map_err(|rejection| match rejection {
+				Rejection::Invalid => Error::Invitation,
+				Rejection::Expired => Error::InvitationExpired,
+				Rejection::Used => Error::InvitationUsed,
+			})?;
+
+		let result = Self::register_unchecked(config, request)?;
+		invitation.0.spend(ledger);
+
+		Ok(result)
+	}
+}
+
+#[test]
+fn invitation() -> anyhow::Result<()> {
+	use crate::{ClientConfig, ClientRegistration};
+
+	const PASSWORD: &[u8] = b"password";
+	const VALIDITY: Duration = Duration::from_secs(60);
+	let server_config = ServerConfig::default().with_invitation_required(true);
+	let server_config: ServerConfig = bincode::deserialize(&bincode::serialize(&server_config)?)?;
+	assert!(server_config.invitation_required());
+	let client_config = ClientConfig::default();
+	let mut ledger = TokenLedger::new();
+
+	let invitation = Invitation::issue(&server_config, "user@example.com", VALIDITY);
+	let invitation: Invitation = bincode::deserialize(&bincode::serialize(&invitation)?)?;
+	assert_eq!(invitation.identifier(), "user@example.com");
+
+	// registration process
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register_invited(
+		&server_config,
+		request.clone(),
+		Some(&invitation),
+		&mut ledger,
+	)?;
+	let (_, finalization, _) = client.finish(response)?;
+	let _file = server.finish(finalization)?;
+
+	// plain registration
+	assert_eq!(
+		ServerRegistration::register(&server_config, request.clone()).map(|_| ()),
+		Err(Error::InvitationMissing)
+	);
+	assert!(!server_config.to_recovery().invitation_required());
+
+	// reuse
+	assert_eq!(
+		ServerRegistration::register_invited(
+			&server_config,
+			request.clone(),
+			Some(&invitation),
+			&mut ledger
+		),
+		Err(Error::InvitationUsed)
+	);
+
+	// missing
+	assert_eq!(
+		ServerRegistration::register_invited(&server_config, request.clone(), None, &mut ledger),
+		Err(Error::InvitationMissing)
+	);
+
+	// tampered identifier
+	let mut tampered = bincode::serialize(&Invitation::issue(
+		&server_config,
+		"user@example.com",
+		VALIDITY,
+	))?;
+	// skip the `TokenId` and the length of the identifier
+	tampered[16 + 8] = b'v';
+	let tampered: Invitation = bincode::deserialize(&tampered)?;
+	assert_eq!(tampered.identifier(), "vser@example.com");
+	assert_eq!(
+		ServerRegistration::register_invited(
+			&server_config,
+			request.clone(),
+			Some(&tampered),
+			&mut ledger
+		),
+		Err(Error::Invitation)
+	);
+
+	// different server
+	let other = Invitation::issue(&ServerConfig::default(), "user@example.com", VALIDITY);
+	assert_eq!(
+		ServerRegistration::register_invited(
+			&server_config,
+			request.clone(),
+			Some(&other),
+			&mut ledger
+		),
+		Err(Error::Invitation)
+	);
+
+	// revoked
+	let revoked = Invitation::issue(&server_config, "user@example.com", VALIDITY);
+	ledger.revoke(revoked.id(), Some(revoked.expires()));
+	assert_eq!(
+		ServerRegistration::register_invited(
+			&server_config,
+			request.clone(),
+			Some(&revoked),
+			&mut ledger
+		),
+		Err(Error::InvitationUsed)
+	);
+
+	// expired
+	let expired = Invitation::issue(&server_config, "user@example.com", Duration::ZERO);
+	assert_eq!(
+		ServerRegistration::register_invited(&server_config, request, Some(&expired), &mut ledger),
+		Err(Error::InvitationExpired)
+	);
+
+	Ok(())
+}
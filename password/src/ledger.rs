@@ -0,0 +1,236 @@
+//! See [`TokenLedger`].
+
+use std::{
+	collections::BTreeMap,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{crypto, Result, ServerConfig};
+
+/// Unique identifier of a single-use token, e.g. a
+/// [`ResumptionTicket`](crate::ResumptionTicket).
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct TokenId([u8; 16]);
+
+impl TokenId {
+	/// Generates a new random [`TokenId`].
+	pub(crate) fn new() -> Self {
+		Self(crypto::random())
+	}
+
+	/// Returns the bytes of this [`TokenId`].
+	#[must_use]
+	pub const fn as_bytes(&self) -> &[u8; 16] {
+		&self.0
+	}
+}
+
+/// Keeps track of used and revoked tokens until they expire. This is meant to
+/// be stored on the server and shared between all instances using the same
+/// [`ServerConfig`](crate::ServerConfig).
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TokenLedger {
+	/// Used or revoked tokens and their expiry in seconds since the Unix epoch.
+	tokens: BTreeMap<TokenId, u64>,
+}
+
+impl TokenLedger {
+	/// Creates an empty [`TokenLedger`].
+	#[must_use]
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns `true` if the token was used or revoked.
+	#[must_use]
+	pub fn contains(&self, id: TokenId) -> bool {
+		self.tokens.contains_key(&id)
+	}
+
+	/// Revokes a token. `expires` is used to [`prune()`](Self::prune) it,
+	/// pass [`None`] if it isn't known.
+	pub fn revoke(&mut self, id: TokenId, expires: Option<SystemTime>) {
+		self.tokens
+			.insert(id, expires.map_or(u64::MAX, to_timestamp));
+	}
+
+	/// Removes all expired tokens, they are rejected because of their expiry
+	/// anyway.
+	pub fn prune(&mut self) {
+		let now = now();
+		self.tokens.retain(|_, expires| *expires > now);
+	}
+
+	/// Returns the number of tracked tokens.
+	#[must_use]
+	pub fn len(&self) -> usize {
+		self.tokens.len()
+	}
+
+	/// Returns `true` if no tokens are tracked.
+	#[must_use]
+	pub fn is_empty(&self) -> bool {
+		self.tokens.is_empty()
+	}
+
+	/// Marks a token as used. Returns `false` if it was already used or
+	/// revoked.
+	pub(crate) fn spend(&mut self, id: TokenId, expires: u64) -> bool {
+		if self.contains(id) {
+			false
+		} else {
+			self.tokens.insert(id, expires);
+			true
+		}
+	}
+}
+
+/// Single-use token bound to an identifier and authenticated with a key
+/// derived from the [`ServerConfig`]. Shared by
+/// [`Invitation`](crate::Invitation) and [`ResetToken`](crate::ResetToken),
+/// which use different keys.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct IdentifierToken {
+	/// Identifies this token in a [`TokenLedger`].
+	id: TokenId,
+	/// Identifier the token was issued to.
+	identifier: String,
+	/// Expiry in seconds since the Unix epoch.
+	expires: u64,
+	/// MAC over all other fields.
+	tag: [u8; 32],
+}
+
+/// Reason an [`IdentifierToken`] was rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Rejection {
+	/// The token wasn't issued by this [`ServerConfig`] or was tampered with.
+	Invalid,
+	/// The token expired.
+	Expired,
+	/// The token was already used or revoked.
+	Used,
+}
+
+impl IdentifierToken {
+	/// Issues a new [`IdentifierToken`] for `identifier`, valid for
+	/// `validity`, authenticated with the key derived with `info`.
+	pub(crate) fn issue(
+		config: &ServerConfig,
+		info: &[u8],
+		identifier: &str,
+		validity: Duration,
+	) -> Self {
+		let id = TokenId::new();
+		let expires = expiry(validity);
+		let tag = crypto::mac(&config.derive_key(info), &[
+			id.as_bytes(),
+			&expires.to_be_bytes(),
+			identifier.as_bytes(),
+		]);
+
+		Self {
+			id,
+			identifier: identifier.to_owned(),
+			expires,
+			tag,
+		}
+	}
+
+	/// Returns the [`TokenId`] of this [`IdentifierToken`].
+	pub(crate) const fn id(&self) -> TokenId {
+		self.id
+	}
+
+	/// Returns the identifier this [`IdentifierToken`] was issued to.
+	pub(crate) fn identifier(&self) -> &str {
+		&self.identifier
+	}
+
+	/// Returns the expiry of this [`IdentifierToken`].
+	pub(crate) fn expires(&self) -> SystemTime {
+		from_timestamp(self.expires)
+	}
+
+	/// Checks that this [`IdentifierToken`] was issued with the key derived
+	/// with `info`, didn't expire and wasn't used or revoked. Doesn't mark it
+	/// as used, see [`spend()`](Self::spend).
+	pub(crate) fn check(
+		&self,
+		config: &ServerConfig,
+		info: &[u8],
+		ledger: &TokenLedger,
+	) -> Result<(), Rejection> {
+		if !crypto::verify(
+			&config.derive_key(info),
+			&[
+				self.id.as_bytes(),
+				&self.expires.to_be_bytes(),
+				self.identifier.as_bytes(),
+			],
+			&self.tag,
+		) {
+			return Err(Rejection::Invalid);
+		}
+
+		if now() >= self.expires {
+			return Err(Rejection::Expired);
+		}
+
+		if ledger.contains(self.id) {
+			return Err(Rejection::Used);
+		}
+
+		Ok(())
+	}
+
+	/// Marks this [`IdentifierToken`] as used in `ledger`.
+	pub(crate) fn spend(&self, ledger: &mut TokenLedger) {
+		ledger.spend(self.id, self.expires);
+	}
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub(crate) fn now() -> u64 {
+	to_timestamp(SystemTime::now())
+}
+
+/// Returns the time in seconds since the Unix epoch `validity` from now.
+pub(crate) fn expiry(validity: Duration) -> u64 {
+	now().saturating_add(validity.as_secs())
+}
+
+/// Converts [`SystemTime`] to seconds since the Unix epoch.
+pub(crate) fn to_timestamp(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_secs())
+}
+
+/// Converts seconds since the Unix epoch to [`SystemTime`].
+pub(crate) fn from_timestamp(timestamp: u64) -> SystemTime {
+	UNIX_EPOCH
+		.checked_add(Duration::from_secs(timestamp))
+		.unwrap_or(UNIX_EPOCH)
+}
+
+#[test]
+fn ledger() {
+	let mut ledger = TokenLedger::new();
+	let used = TokenId::new();
+	let expired = TokenId::new();
+	let revoked = TokenId::new();
+
+	assert!(ledger.spend(used, expiry(Duration::from_secs(60))));
+	assert!(!ledger.spend(used, expiry(Duration::from_secs(60))));
+	assert!(ledger.spend(expired, now()));
+	ledger.revoke(revoked, None);
+	assert!(!ledger.spend(revoked, expiry(Duration::from_secs(60))));
+	assert_eq!(ledger.len(), 3);
+
+	ledger.prune();
+	assert!(ledger.contains(used));
+	assert!(!ledger.contains(expired));
+	assert!(ledger.contains(revoked));
+}
@@ -73,31 +73,93 @@
 // TODO: expose server keypair with types from `custodian-shared` and enable
 // optional external keypairs
 
+mod breach;
+mod channel;
 pub(crate) mod cipher_suite;
 mod client;
 mod config;
+mod crypto;
+mod deletion;
 pub mod error;
+mod escrow;
 mod export_key;
+mod hardening;
+mod invitation;
+mod ledger;
+mod locked;
 mod message;
+mod migration;
+mod oblivious;
+mod oprf;
+mod password;
+mod pow;
 mod public_key;
+mod reauth;
+mod recipient;
+mod recovery;
+mod reset;
+mod resumption;
 mod server;
+mod session_key;
+mod social;
+#[cfg(feature = "zxcvbn")]
+mod strength;
+#[cfg(feature = "token")]
+mod token;
+mod totp;
+mod wrapped_key;
 
 pub use arrayvec;
 pub use serde;
 
 #[cfg(feature = "pbkdf2")]
 pub use crate::config::{Pbkdf2Hash, Pbkdf2Params};
+#[cfg(feature = "zxcvbn")]
+pub use crate::strength::{Feedback, Score, Strength, Suggestion, Warning};
+#[cfg(feature = "token")]
+pub use crate::token::{Claims, Token, TokenVerifier};
 pub use crate::{
+	breach::{BreachCorpus, BreachFilter},
+	channel::SecureChannel,
 	client::{ClientConfig, ClientFile, ClientLogin, ClientRegistration},
-	config::{Ake, Argon2Algorithm, Argon2Params, Config, Group, Hash, Mhf},
+	config::{
+		Ake, Argon2Algorithm, Argon2Params, Config, Group, Hash, Mhf, Normalization, PasswordPolicy,
+	},
+	deletion::{
+		ClientDeletion, DeletionAuthorization, DeletionFinalization, DeletionResponse,
+		ServerDeletion,
+	},
 	error::{Error, Result},
+	escrow::{Escrow, EscrowKey},
 	export_key::ExportKey,
+	hardening::{
+		HardenedPassword, HardeningClient, HardeningRequest, HardeningResponse, HardeningServer,
+		UpdateToken,
+	},
+	invitation::Invitation,
+	ledger::{TokenId, TokenLedger},
 	message::{
 		LoginFinalization, LoginRequest, LoginResponse, RegistrationFinalization,
 		RegistrationRequest, RegistrationResponse,
 	},
+	migration::{ClientMigration, LegacyAlgorithm, LegacyHash, MigrationRequest, ServerMigration},
+	oblivious::{BreachClient, BreachRequest, BreachResponse, BreachServer},
+	password::Password,
+	pow::{Challenge, Difficulty, Solution},
 	public_key::PublicKey,
-	server::{ServerConfig, ServerFile, ServerLogin, ServerRegistration},
+	reauth::{ClientReauth, ReauthChallenge, ReauthProof, ReauthResponse, ServerReauth},
+	recipient::{RecipientPublicKey, RecipientSecretKey},
+	recovery::{RecoveryCode, RecoveryFile, ServerRecovery},
+	reset::{DataAccess, ResetToken, ServerReset},
+	resumption::{ClientTicket, ResumptionRequest, ResumptionTicket},
+	server::{
+		PasswordStatus, RegistrationOutcome, ServerConfig, ServerFile, ServerLogin,
+		ServerRegistration,
+	},
+	session_key::SessionKey,
+	social::{EncryptedShare, Share},
+	totp::{EncryptedTotp, ServerTotp, TotpSecret},
+	wrapped_key::{DataKey, KeySlot, WrappedKey},
 };
 
 #[test]
@@ -120,9 +182,12 @@ fn basic() -> anyhow::Result<()> {
 
 	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
 
-	let (_, finalization, _) = client.finish(response)?;
+	let (_, finalization, _, client_session_key) = client.finish(response)?;
 
-	server.finish(finalization)?;
+	let (server_session_key, status) = server.finish(finalization)?;
+
+	assert_eq!(client_session_key, server_session_key);
+	assert_eq!(status, PasswordStatus::Permanent);
 
 	Ok(())
 }
@@ -140,7 +205,7 @@ fn consistency() -> anyhow::Result<()> {
 
 	let (client, request) = ClientLogin::login(client_config, None, PASSWORD)?;
 	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
-	let (new_client_file, finalization, new_export_key) = client.finish(response)?;
+	let (new_client_file, finalization, new_export_key, _) = client.finish(response)?;
 	server.finish(finalization)?;
 
 	assert_eq!(client_file, new_client_file);
@@ -170,7 +235,7 @@ fn not_validated() -> anyhow::Result<()> {
 	assert_eq!(client.config().public_key(), None);
 
 	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
-	let (_, finalization, _) = client.finish(response)?;
+	let (_, finalization, ..) = client.finish(response)?;
 	server.finish(finalization)?;
 
 	Ok(())
@@ -205,6 +270,269 @@ fn no_client() -> anyhow::Result<()> {
 	Ok(())
 }
 
+#[test]
+fn temporary() -> anyhow::Result<()> {
+	const TEMPORARY: &[u8] = b"temporary";
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	// administrator provisions the account
+	let (client, request) = ClientRegistration::register(client_config, TEMPORARY)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, _) = client.finish(response)?;
+	let server_file = server.finish_temporary(finalization)?;
+	assert_eq!(server_file.password_status(), PasswordStatus::Temporary);
+	let server_file: ServerFile = bincode::deserialize(&bincode::serialize(&server_file)?)?;
+
+	// first login has to change the password
+	let (client, request) = ClientLogin::login(client_config, None, TEMPORARY)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (client_file, finalization, ..) = client.finish(response)?;
+	let (_, status) = server.finish(finalization)?;
+	assert_eq!(status, PasswordStatus::Temporary);
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	let (_, status) = server.finish(finalization)?;
+	assert_eq!(status, PasswordStatus::Permanent);
+
+	Ok(())
+}
+
+#[test]
+fn private_registration() -> anyhow::Result<()> {
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	// new client
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, _) = client.finish(response)?;
+	let server_file = match server.finish_private(finalization, None)? {
+		RegistrationOutcome::Registered(file) => file,
+		RegistrationOutcome::Existing => unreachable!("client wasn't registered"),
+	};
+
+	// existing client completes the protocol the same way
+	let (client, request) = ClientRegistration::register(client_config, b"other")?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, _) = client.finish(response)?;
+	assert_eq!(
+		server.finish_private(finalization, Some(&server_file))?,
+		RegistrationOutcome::Existing
+	);
+
+	// existing `ServerFile` stays valid
+	let (client, request) = ClientLogin::login(client_config, None, PASSWORD)?;
+	let (server, response) =
+		ServerLogin::login(&server_config, Some(server_file.clone()), request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	server.finish(finalization)?;
+
+	// different config
+	let config = Config::new(
+		Ake::X25519,
+		Group::default(),
+		Hash::default(),
+		Mhf::default(),
+	);
+	let (client, request) =
+		ClientRegistration::register(ClientConfig::new(config, None)?, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&ServerConfig::new(config), request)?;
+	let (_, finalization, _) = client.finish(response)?;
+	assert_eq!(
+		server.finish_private(finalization, Some(&server_file)),
+		Err(Error::Config)
+	);
+
+	Ok(())
+}
+
+#[test]
+fn redacted() -> anyhow::Result<()> {
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	assert_eq!(
+		format!("{:?}", client),
+		format!("ClientRegistration {{ config: {:?}, .. }}", client_config)
+	);
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, export_key) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+	assert_eq!(format!("{:?}", export_key), "ExportKey { .. }");
+
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	assert!(format!("{:?}", client).ends_with(", .. }"));
+	let (server, _) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	assert!(format!("{:?}", server).ends_with(", .. }"));
+
+	let debug = format!("{:?}", server_config);
+	assert!(!debug.contains("setup") && !debug.contains("secret"));
+
+	// equality
+	let copy: ServerConfig = bincode::deserialize(&bincode::serialize(&server_config)?)?;
+	assert_eq!(copy, server_config);
+	assert_eq!(copy.derive_key(b"test"), server_config.derive_key(b"test"));
+	assert_ne!(ServerConfig::default(), server_config);
+
+	Ok(())
+}
+
+#[test]
+fn normalization() -> anyhow::Result<()> {
+	use unicode_normalization::UnicodeNormalization;
+
+	// "é" composed (NFC) and decomposed (NFD), separated by a no-break space
+	const NFC: &str = "caf\u{e9}\u{a0}au lait";
+	const NFD: &str = "cafe\u{301} au lait";
+	let config = Config::default().with_normalization(Normalization::OpaqueString);
+	let server_config = ServerConfig::new(config);
+	let client_config = ClientConfig::new(config, None)?;
+
+	let (client, request) = ClientRegistration::register(client_config, Password::from(NFC))?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	let (client, request) =
+		ClientLogin::login(client_config, Some(client_file), Password::from(NFD))?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	server.finish(finalization)?;
+
+	// rejected passwords
+	assert_eq!(
+		ClientRegistration::register(client_config, "").map(|_| ()),
+		Err(Error::Password)
+	);
+	assert_eq!(
+		ClientRegistration::register(client_config, "pass\nword").map(|_| ()),
+		Err(Error::Password)
+	);
+	assert_eq!(
+		ClientRegistration::register(client_config, [0xff]).map(|_| ()),
+		Err(Error::Password)
+	);
+
+	// NFKC folds compatibility characters
+	assert_eq!(
+		Normalization::Nfkc
+			.normalize("\u{fb01}".as_bytes())?
+			.as_slice(),
+		b"fi"
+	);
+	// expansion beyond the input length
+	assert_eq!(
+		Normalization::Nfkc
+			.normalize("\u{fdfa}".as_bytes())?
+			.as_slice(),
+		"\u{fdfa}".nfkc().collect::<String>().as_bytes()
+	);
+	assert_eq!(Normalization::None.normalize(&[0xff])?.as_slice(), [0xff]);
+
+	// configs with different normalizations don't match
+	assert_ne!(config, Config::default());
+	assert_eq!(Password::from(NFC), Password::from(NFC.to_owned()));
+	assert_eq!(format!("{:?}", Password::from(NFC)), "Password { .. }");
+
+	Ok(())
+}
+
+#[test]
+fn policy() -> anyhow::Result<()> {
+	/// Registers `password` against `server_config`.
+	fn register(
+		server_config: &ServerConfig,
+		password: &str,
+		banned: &[&str],
+	) -> Result<(ClientFile, ServerFile)> {
+		let client_config = ClientConfig::new(server_config.config(), None)?;
+		let (client, request) =
+			ClientRegistration::register_with_banned(client_config, password, banned)?;
+		let (server, response) = ServerRegistration::register(server_config, request)?;
+		let (client_file, finalization, _) = client.finish(response)?;
+		let server_file = server.finish(finalization)?;
+
+		Ok((client_file, server_file))
+	}
+
+	let policy = PasswordPolicy::new(8, 16)?;
+	let server_config = ServerConfig::default().with_policy(policy);
+	let client_config = ClientConfig::new(server_config.config(), None)?;
+
+	assert_eq!(server_config.policy(), policy);
+	assert_eq!(policy.min_length(), 8);
+	assert_eq!(policy.max_length(), 16);
+	assert_eq!(PasswordPolicy::new(16, 8), Err(Error::PasswordPolicy));
+	assert_eq!(PasswordPolicy::new(None, None)?, PasswordPolicy::default());
+
+	// the server dictates the policy
+	let (_, request) = ClientRegistration::register(client_config, "correct horse")?;
+	let (_, response) = ServerRegistration::register(&server_config, request)?;
+	assert_eq!(response.policy(), policy);
+
+	let (client_file, server_file) = register(&server_config, "correct horse", &["alice"])?;
+
+	// login isn't affected by the policy, even after it changed
+	let server_config = server_config.with_policy(PasswordPolicy::new(16, None)?);
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), "correct horse")?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	server.finish(finalization)?;
+
+	// violations
+	let server_config = server_config.with_policy(policy);
+	assert_eq!(
+		register(&server_config, "short", &[]).map(|_| ()),
+		Err(Error::PasswordTooShort(8))
+	);
+	assert_eq!(
+		register(&server_config, "much too long password", &[]).map(|_| ()),
+		Err(Error::PasswordTooLong(16))
+	);
+	assert_eq!(
+		register(&server_config, "ALICE's password", &["", "Alice"]).map(|_| ()),
+		Err(Error::PasswordBanned(String::from("Alice")))
+	);
+
+	// guessable passwords
+	#[cfg(feature = "zxcvbn")]
+	{
+		let strict = server_config
+			.clone()
+			.with_policy(policy.with_min_score(Score::Strong));
+		assert_eq!(strict.policy().min_score(), Score::Strong);
+		assert!(matches!(
+			register(&strict, "Password1", &[]).map(|_| ()),
+			Err(Error::PasswordWeak(feedback)) if feedback.warning().is_some()
+		));
+		assert!(matches!(
+			register(&strict, "zorblax2020", &["zorblax"]).map(|_| ()),
+			Err(Error::PasswordBanned(_))
+		));
+		let (..) = register(&strict, "kV8#qz!W2m$Lp", &[])?;
+	}
+
+	// lengths are counted in characters
+	let (..) = register(&server_config, &"\u{1f600}".repeat(16), &[])?;
+
+	// recovery codes aren't subject to the policy
+	assert_eq!(server_config.to_recovery().policy(), PasswordPolicy::NONE);
+
+	Ok(())
+}
+
 #[test]
 fn wrong_server_register() -> anyhow::Result<()> {
 	let server_config = ServerConfig::default();
@@ -338,7 +666,7 @@ fn cipher_suites() -> anyhow::Result<()> {
 		let (server, response) =
 			ServerLogin::login(&server_config, Some(server_file.clone()), request)?;
 		let (server, response) = (serialize(&server)?, serialize(&response)?);
-		let (new_client_file, finalization, new_export_key) = client.finish(response)?;
+		let (new_client_file, finalization, new_export_key, _) = client.finish(response)?;
 		let (new_client_file, finalization, new_export_key) = (
 			serialize(&new_client_file)?,
 			serialize(&finalization)?,
@@ -502,7 +830,7 @@ fn wrong_config() -> anyhow::Result<()> {
 	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
 	let (server, response) =
 		ServerLogin::login(&server_config, Some(server_file.clone()), request.clone())?;
-	let (_, finalization, _) = client.clone().finish(response.clone())?;
+	let (_, finalization, ..) = client.clone().finish(response.clone())?;
 	server.clone().finish(finalization.clone())?;
 
 	let (wrong_client, wrong_request) =
@@ -512,7 +840,7 @@ fn wrong_config() -> anyhow::Result<()> {
 		Some(wrong_server_file.clone()),
 		wrong_request.clone(),
 	)?;
-	let (_, wrong_finalization, _) = wrong_client.clone().finish(wrong_response.clone())?;
+	let (_, wrong_finalization, ..) = wrong_client.clone().finish(wrong_response.clone())?;
 	wrong_server.clone().finish(wrong_finalization.clone())?;
 
 	assert_eq!(
@@ -602,7 +930,7 @@ fn getters() -> anyhow::Result<()> {
 	assert_eq!(server.config(), config);
 	assert_eq!(response.config(), config);
 
-	let (new_client_file, finalization, export_key) = client.finish(response)?;
+	let (new_client_file, finalization, export_key, _) = client.finish(response)?;
 
 	assert_eq!(new_client_file.config(), config);
 	assert_eq!(new_client_file.public_key(), public_key);
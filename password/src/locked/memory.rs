@@ -0,0 +1,162 @@
+//! See [`Memory`].
+
+use std::{
+	convert::TryFrom,
+	io,
+	mem::{align_of, size_of},
+	ops::{Deref, DerefMut},
+	ptr::{self, NonNull},
+	slice,
+};
+
+use zeroize::Zeroize;
+
+/// Value stored in its own anonymous mapping: `mlock`ed data pages between two
+/// inaccessible guard pages, excluded from core dumps on Linux.
+pub(crate) struct Memory<T> {
+	/// Start of the mapping, including the guard pages.
+	mapping: NonNull<u8>,
+	/// Size of a page.
+	page: usize,
+	/// Size of the data pages.
+	size: usize,
+	/// `true` if the data pages were locked.
+	locked: bool,
+	/// Value stored in the data pages.
+	value: NonNull<T>,
+}
+
+// SAFETY: `Memory` owns its value like a `Box`.
+unsafe impl<T: Send> Send for Memory<T> {}
+// SAFETY: `Memory` owns its value like a `Box`.
+unsafe impl<T: Sync> Sync for Memory<T> {}
+
+impl<T> Memory<T> {
+	/// Moves `value` into a new mapping.
+	///
+	/// # Errors
+	/// Returns `value` back if the mapping couldn't be created.
+	pub(crate) fn new(value: T) -> Result<Self, (T, io::Error)> {
+		let page = page_size();
+
+		if align_of::<T>() > page {
+			return Err((value, io::Error::from(io::ErrorKind::InvalidInput)));
+		}
+
+		let size = round_up(size_of::<T>().max(1), page);
+		let total = size + 2 * page;
+
+		// SAFETY: requests a new anonymous mapping, no existing memory is affected.
+		let mapping = unsafe {
+			libc::mmap(
+				ptr::null_mut(),
+				total,
+				libc::PROT_NONE,
+				libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+				-1,
+				0,
+			)
+		};
+
+		if mapping == libc::MAP_FAILED {
+			return Err((value, io::Error::last_os_error()));
+		}
+
+		let mapping = NonNull::new(mapping.cast::<u8>()).expect("mapping is null");
+		// SAFETY: the data pages start after the first guard page, inside the
+		// mapping.
+		let data = unsafe { mapping.as_ptr().add(page) };
+
+		// SAFETY: `data` and `size` are inside the mapping and page aligned.
+		if unsafe { libc::mprotect(data.cast(), size, libc::PROT_READ | libc::PROT_WRITE) } != 0 {
+			let error = io::Error::last_os_error();
+			// SAFETY: the mapping was created above and isn't used afterwards.
+			unsafe { libc::munmap(mapping.as_ptr().cast(), total) };
+
+			return Err((value, error));
+		}
+
+		#[cfg(target_os = "linux")]
+		// SAFETY: `data` and `size` are inside the mapping and page aligned.
+		if unsafe { libc::madvise(data.cast(), size, libc::MADV_DONTDUMP) } != 0 {
+			log::warn!(
+				"failed to exclude secret from core dumps: {}",
+				io::Error::last_os_error()
+			);
+		}
+
+		// SAFETY: `data` and `size` are inside the mapping and page aligned.
+		let locked = unsafe { libc::mlock(data.cast(), size) } == 0;
+
+		if !locked {
+			log::warn!(
+				"failed to lock secret in memory, check `RLIMIT_MEMLOCK`: {}",
+				io::Error::last_os_error()
+			);
+		}
+
+		let value_ptr = data.cast::<T>();
+		// SAFETY: `value_ptr` is aligned, writable and large enough for `T`.
+		unsafe { value_ptr.write(value) };
+
+		Ok(Self {
+			mapping,
+			page,
+			size,
+			locked,
+			value: NonNull::new(value_ptr).expect("data is null"),
+		})
+	}
+
+	/// Returns `true` if the data pages are locked.
+	pub(crate) const fn is_locked(&self) -> bool {
+		self.locked
+	}
+}
+
+impl<T> Deref for Memory<T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		// SAFETY: `value` is initialized until `drop`.
+		unsafe { self.value.as_ref() }
+	}
+}
+
+impl<T> DerefMut for Memory<T> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		// SAFETY: `value` is initialized until `drop`.
+		unsafe { self.value.as_mut() }
+	}
+}
+
+impl<T> Drop for Memory<T> {
+	fn drop(&mut self) {
+		let data = self.value.as_ptr().cast::<u8>();
+
+		// SAFETY: `value` is initialized and not used afterwards, the data pages
+		// are writable and unmapped afterwards.
+		unsafe {
+			ptr::drop_in_place(self.value.as_ptr());
+			slice::from_raw_parts_mut(data, self.size).zeroize();
+
+			if self.locked {
+				libc::munlock(data.cast(), self.size);
+			}
+
+			libc::munmap(self.mapping.as_ptr().cast(), self.size + 2 * self.page);
+		}
+	}
+}
+
+/// Returns the size of a page.
+pub(super) fn page_size() -> usize {
+	// SAFETY: `sysconf` has no preconditions.
+	let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+	usize::try_from(page).expect("invalid page size")
+}
+
+/// Rounds `size` up to a multiple of `page`, which is a power of two.
+const fn round_up(size: usize, page: usize) -> usize {
+	(size + page - 1) & !(page - 1)
+}
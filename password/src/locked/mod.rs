@@ -0,0 +1,165 @@
+//! See [`Locked`].
+
+#[cfg(all(feature = "locked-memory", unix))]
+#[allow(unsafe_code)]
+mod memory;
+
+use std::{
+	fmt::{self, Debug, Formatter},
+	ops::{Deref, DerefMut},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Storage for long-lived secrets.
+///
+/// With the `locked-memory` crate feature on Unix, the value is stored in
+/// `mlock`ed pages surrounded by guard pages and excluded from core dumps. If
+/// the pages can't be locked, e.g. because `RLIMIT_MEMLOCK` is too low, a
+/// warning is logged and the pages stay unlocked. If the pages can't be
+/// mapped at all, a warning is logged and the value is stored inline.
+///
+/// Without the `locked-memory` crate feature or on other platforms, where it
+/// has no effect, the value is stored inline. Mapped pages are overwritten
+/// with zeros when dropped, values stored inline rely on their own
+/// [`Zeroize`](zeroize::Zeroize) implementation.
+pub(crate) struct Locked<T>(
+	#[cfg(all(feature = "locked-memory", unix))] Storage<T>,
+	#[cfg(not(all(feature = "locked-memory", unix)))] T,
+);
+
+/// Storage of a [`Locked`] value.
+#[cfg(all(feature = "locked-memory", unix))]
+enum Storage<T> {
+	/// Stored in its own mapping.
+	Mapped(memory::Memory<T>),
+	/// Stored inline because the mapping couldn't be created.
+	Inline(T),
+}
+
+impl<T> Locked<T> {
+	/// Moves `value` into a [`Locked`].
+	#[cfg(all(feature = "locked-memory", unix))]
+	pub(crate) fn new(value: T) -> Self {
+		match memory::Memory::new(value) {
+			Ok(memory) => Self(Storage::Mapped(memory)),
+			Err((value, error)) => {
+				log::warn!(
+					"failed to map memory for secret, storing it inline: {}",
+					error
+				);
+				Self(Storage::Inline(value))
+			}
+		}
+	}
+
+	/// Moves `value` into a [`Locked`].
+	#[cfg(not(all(feature = "locked-memory", unix)))]
+	pub(crate) const fn new(value: T) -> Self {
+		Self(value)
+	}
+
+	/// Returns `true` if the value is stored in locked pages.
+	#[cfg(all(feature = "locked-memory", unix))]
+	pub(crate) const fn is_locked(&self) -> bool {
+		match &self.0 {
+			Storage::Mapped(memory) => memory.is_locked(),
+			Storage::Inline(_) => false,
+		}
+	}
+
+	/// Returns `true` if the value is stored in locked pages.
+	#[allow(clippy::unused_self)]
+	#[cfg(not(all(feature = "locked-memory", unix)))]
+	pub(crate) const fn is_locked(&self) -> bool {
+		false
+	}
+}
+
+impl<T> Deref for Locked<T> {
+	type Target = T;
+
+	#[cfg(all(feature = "locked-memory", unix))]
+	fn deref(&self) -> &Self::Target {
+		match &self.0 {
+			Storage::Mapped(memory) => memory,
+			Storage::Inline(value) => value,
+		}
+	}
+
+	#[cfg(not(all(feature = "locked-memory", unix)))]
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl<T> DerefMut for Locked<T> {
+	#[cfg(all(feature = "locked-memory", unix))]
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		match &mut self.0 {
+			Storage::Mapped(memory) => memory,
+			Storage::Inline(value) => value,
+		}
+	}
+
+	#[cfg(not(all(feature = "locked-memory", unix)))]
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl<T: Clone> Clone for Locked<T> {
+	fn clone(&self) -> Self {
+		Self::new(T::clone(self))
+	}
+}
+
+impl<T> Debug for Locked<T> {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Locked").finish_non_exhaustive()
+	}
+}
+
+impl<T: Serialize> Serialize for Locked<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		T::serialize(self, serializer)
+	}
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Locked<T> {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		T::deserialize(deserializer).map(Self::new)
+	}
+}
+
+#[test]
+fn locked() {
+	let value = Locked::new([1_u8; 32]);
+	assert_eq!(*value, [1; 32]);
+
+	let mut copy = value.clone();
+	assert_eq!(*copy, *value);
+	copy[0] = 0;
+	assert_ne!(*copy, *value);
+
+	#[cfg(all(feature = "locked-memory", unix))]
+	assert_eq!(value.is_locked(), copy.is_locked());
+}
+
+#[test]
+#[cfg(all(feature = "locked-memory", unix))]
+fn fallback() {
+	/// Aligned to more than a page on most platforms, which can't be mapped.
+	#[derive(Debug, Eq, PartialEq)]
+	#[repr(align(16384))]
+	struct Aligned(u8);
+
+	if std::mem::align_of::<Aligned>() <= memory::page_size() {
+		return;
+	}
+
+	let value = Locked::new(Aligned(1));
+	assert!(matches!(value.0, Storage::Inline(_)));
+	assert!(!value.is_locked());
+	assert_eq!(*value, Aligned(1));
+}
@@ -3,7 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::{cipher_suite, Config};
+use crate::{cipher_suite, Config, Escrow, PasswordPolicy};
 
 /// Send this to the server to drive the registration process. See
 /// [`ServerRegistration::register()`](crate::ServerRegistration::register).
@@ -33,6 +33,8 @@ pub struct RegistrationResponse {
 	pub(crate) config: Config,
 	/// Wrapped [opaque-ke](opaque_ke) type.
 	pub(crate) message: cipher_suite::RegistrationResponse,
+	/// [`PasswordPolicy`] of the server.
+	pub(crate) policy: PasswordPolicy,
 }
 
 impl RegistrationResponse {
@@ -41,6 +43,13 @@ impl RegistrationResponse {
 	pub const fn config(&self) -> Config {
 		self.config
 	}
+
+	/// Returns the [`PasswordPolicy`] dictated by the server, see
+	/// [`ServerConfig::with_policy()`](crate::ServerConfig::with_policy).
+	#[must_use]
+	pub const fn policy(&self) -> PasswordPolicy {
+		self.policy
+	}
 }
 
 /// Send this back to the server to finish the registration process. See
@@ -52,6 +61,9 @@ pub struct RegistrationFinalization {
 	pub(crate) config: Config,
 	/// Wrapped [opaque-ke](opaque_ke) type.
 	pub(crate) message: cipher_suite::RegistrationFinalization,
+	/// [`Escrow`] if enabled, see
+	/// [`ClientConfig::with_escrow()`](crate::ClientConfig::with_escrow).
+	pub(crate) escrow: Option<Escrow>,
 }
 
 impl RegistrationFinalization {
@@ -60,6 +72,13 @@ impl RegistrationFinalization {
 	pub const fn config(&self) -> Config {
 		self.config
 	}
+
+	/// Returns the [`Escrow`] to store if escrow is enabled, see
+	/// [`ClientConfig::with_escrow()`](crate::ClientConfig::with_escrow).
+	#[must_use]
+	pub const fn escrow(&self) -> Option<&Escrow> {
+		self.escrow.as_ref()
+	}
 }
 
 /// Send this to the server to drive the login process. See
@@ -109,6 +128,9 @@ pub struct LoginFinalization {
 	pub(crate) config: Config,
 	/// Wrapped [opaque-ke](opaque_ke) type.
 	pub(crate) message: cipher_suite::LoginFinalization,
+	/// [`Escrow`] if enabled, see
+	/// [`ClientConfig::with_escrow()`](crate::ClientConfig::with_escrow).
+	pub(crate) escrow: Option<Escrow>,
 }
 
 impl LoginFinalization {
@@ -117,4 +139,11 @@ impl LoginFinalization {
 	pub const fn config(&self) -> Config {
 		self.config
 	}
+
+	/// Returns the [`Escrow`] to store if escrow is enabled, see
+	/// [`ClientConfig::with_escrow()`](crate::ClientConfig::with_escrow).
+	#[must_use]
+	pub const fn escrow(&self) -> Option<&Escrow> {
+		self.escrow.as_ref()
+	}
 }
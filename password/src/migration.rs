@@ -0,0 +1,469 @@
+//! Migration of legacy password hashes to OPAQUE, see [`ServerMigration`].
+
+use std::fmt::{self, Debug, Formatter};
+
+use argon2::{
+	password_hash::{PasswordHash, PasswordVerifier},
+	Argon2,
+};
+#[cfg(feature = "pbkdf2")]
+use hmac::Hmac;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "pbkdf2")]
+use sha2::{Sha256, Sha512};
+#[cfg(feature = "pbkdf2")]
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use crate::{
+	ClientConfig, ClientFile, ClientRegistration, Config, Error, ExportKey,
+	RegistrationFinalization, RegistrationRequest, RegistrationResponse, Result, SecureChannel,
+	ServerConfig, ServerFile, ServerRegistration,
+};
+
+/// Algorithm of a [`LegacyHash`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum LegacyAlgorithm {
+	/// Argon2 in the PHC string format, e.g. `$argon2id$v=19$...`.
+	Argon2,
+	/// bcrypt in the modular crypt format, e.g. `$2b$12$...`.
+	#[cfg(feature = "bcrypt")]
+	Bcrypt,
+	/// PBKDF2 in the PHC string format, `$pbkdf2-sha256$...` or
+	/// `$pbkdf2-sha512$...`.
+	#[cfg(feature = "pbkdf2")]
+	Pbkdf2,
+}
+
+/// Password hash stored by a system predating OPAQUE.
+///
+/// Kept by the server until the user logs in the next time and migrates with
+/// [`ServerMigration::migrate()`].
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub struct LegacyHash {
+	/// Algorithm of the hash.
+	algorithm: LegacyAlgorithm,
+	/// Hash in the PHC string or modular crypt format.
+	hash: String,
+}
+
+impl Debug for LegacyHash {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("LegacyHash")
+			.field("algorithm", &self.algorithm)
+			.finish_non_exhaustive()
+	}
+}
+
+impl LegacyHash {
+	/// Parses a password hash in the PHC string format, or the modular crypt
+	/// format for bcrypt.
+	///
+	/// # Errors
+	/// [`Error::LegacyHash`] if `hash` is malformed or the algorithm is
+	/// unsupported.
+	pub fn new<H: Into<String>>(hash: H) -> Result<Self> {
+		let hash = hash.into();
+
+		#[cfg(feature = "bcrypt")]
+		if ["$2a$", "$2b$", "$2x$", "$2y$"]
+			.iter()
+			.any(|prefix| hash.starts_with(prefix))
+		{
+			hash.parse::<bcrypt_::HashParts>()
+				.map_err(|_| Error::LegacyHash)?;
+
+			return Ok(Self {
+				algorithm: LegacyAlgorithm::Bcrypt,
+				hash,
+			});
+		}
+
+		let parsed = PasswordHash::new(&hash).map_err(|_| Error::LegacyHash)?;
+
+		if parsed.salt.is_none() || parsed.hash.is_none() {
+			return Err(Error::LegacyHash);
+		}
+
+		let algorithm = match parsed.algorithm.as_str() {
+			"argon2d" | "argon2i" | "argon2id" => LegacyAlgorithm::Argon2,
+			#[cfg(feature = "pbkdf2")]
+			"pbkdf2-sha256" | "pbkdf2-sha512" if parsed.params.get_decimal("i").is_some() =>
+				LegacyAlgorithm::Pbkdf2,
+			_ => return Err(Error::LegacyHash),
+		};
+
+		Ok(Self { algorithm, hash })
+	}
+
+	/// Returns the [`LegacyAlgorithm`] of this [`LegacyHash`].
+	#[must_use]
+	pub const fn algorithm(&self) -> LegacyAlgorithm {
+		self.algorithm
+	}
+
+	/// Returns the hash in the format it was parsed from.
+	#[must_use]
+	pub fn as_str(&self) -> &str {
+		&self.hash
+	}
+
+	/// Verifies `password` against this [`LegacyHash`].
+	///
+	/// # Errors
+	/// - [`Error::Credentials`] if `password` doesn't match
+	/// - [`Error::LegacyHash`] if the parameters of this [`LegacyHash`] are
+	///   invalid
+	pub fn verify<P: AsRef<[u8]>>(&self, password: P) -> Result<()> {
+		let password = password.as_ref();
+
+		let valid = match self.algorithm {
+			LegacyAlgorithm::Argon2 => {
+				let hash = PasswordHash::new(&self.hash).map_err(|_| Error::LegacyHash)?;
+				Argon2::default().verify_password(password, &hash).is_ok()
+			}
+			#[cfg(feature = "bcrypt")]
+			LegacyAlgorithm::Bcrypt =>
+				bcrypt_::verify(password, &self.hash).map_err(|_| Error::LegacyHash)?,
+			#[cfg(feature = "pbkdf2")]
+			LegacyAlgorithm::Pbkdf2 => self.verify_pbkdf2(password)?,
+		};
+
+		if valid {
+			Ok(())
+		} else {
+			Err(Error::Credentials)
+		}
+	}
+
+	/// Verifies `password` against this PBKDF2 [`LegacyHash`].
+	#[cfg(feature = "pbkdf2")]
+	fn verify_pbkdf2(&self, password: &[u8]) -> Result<bool> {
+		let hash = PasswordHash::new(&self.hash).map_err(|_| Error::LegacyHash)?;
+		let rounds = hash.params.get_decimal("i").ok_or(Error::LegacyHash)?;
+		let mut salt = [0; 64];
+		let salt = hash
+			.salt
+			.ok_or(Error::LegacyHash)?
+			.b64_decode(&mut salt)
+			.map_err(|_| Error::LegacyHash)?;
+		let expected = hash.hash.ok_or(Error::LegacyHash)?;
+
+		let mut output = Zeroizing::new(vec![0; expected.len()]);
+
+		match hash.algorithm.as_str() {
+			"pbkdf2-sha256" => pbkdf2_::pbkdf2::<Hmac<Sha256>>(password, salt, rounds, &mut output),
+			"pbkdf2-sha512" => pbkdf2_::pbkdf2::<Hmac<Sha512>>(password, salt, rounds, &mut output),
+			_ => return Err(Error::LegacyHash),
+		}
+
+		Ok(output.ct_eq(expected.as_bytes()).into())
+	}
+}
+
+/// Holds the state of a migration on the client. See
+/// [`migrate()`](Self::migrate).
+#[must_use = "Use `finish()` to complete the migration"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClientMigration {
+	/// Underlying registration process.
+	registration: ClientRegistration,
+}
+
+impl ClientMigration {
+	/// Returns the [`ClientConfig`] associated with this [`ClientMigration`].
+	#[must_use]
+	pub const fn config(&self) -> ClientConfig {
+		self.registration.config()
+	}
+
+	/// Starts the migration of a user still stored with a [`LegacyHash`],
+	/// usually when a login fails because no [`ServerFile`] exists yet. The
+	/// returned [`MigrationRequest`] has to be sent to the server. See
+	/// [`ServerMigration::migrate()`].
+	///
+	/// The server needs `password` to verify it against the [`LegacyHash`],
+	/// it's [`seal`](SecureChannel::seal)ed with `channel` and never leaves
+	/// the client in plain text. `channel` has to be established from a
+	/// [`SessionKey`](crate::SessionKey) shared with the server, which opens
+	/// the password in [`ServerMigration::migrate()`].
+	///
+	/// # Errors
+	/// - [`Error::ChannelExhausted`] if no epochs are left in `channel`
+	/// - Same as [`ClientRegistration::register()`]
+	pub fn migrate<P: AsRef<[u8]>>(
+		config: ClientConfig,
+		password: P,
+		channel: &mut SecureChannel,
+	) -> Result<(Self, MigrationRequest)> {
+		let (registration, request) = ClientRegistration::register(config, password.as_ref())?;
+
+		Ok((Self { registration }, MigrationRequest {
+			password: channel.seal(password.as_ref())?,
+			registration: request,
+		}))
+	}
+
+	/// Finishes the migration. The returned [`RegistrationFinalization`] has
+	/// to be sent back to the server. See [`ServerMigration::finish()`].
+	///
+	/// The [`PasswordPolicy`](crate::PasswordPolicy) of the server isn't
+	/// enforced, the password was already accepted by the legacy system and
+	/// rejecting it would leave the user stuck with the [`LegacyHash`]. Force
+	/// a password reset afterwards if it has to comply.
+	///
+	/// # Errors
+	/// Same as [`ClientRegistration::finish()`], except for violations of the
+	/// [`PasswordPolicy`](crate::PasswordPolicy).
+	pub fn finish(
+		self,
+		response: RegistrationResponse,
+	) -> Result<(ClientFile, RegistrationFinalization, ExportKey)> {
+		self.registration.finish_unchecked(response)
+	}
+}
+
+/// Sealed legacy password and registration sent by the client. See
+/// [`ServerMigration::migrate()`].
+#[must_use = "Does nothing if not sent to the server"]
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
+pub struct MigrationRequest {
+	/// Password to verify against the [`LegacyHash`], sealed with the
+	/// [`SecureChannel`].
+	password: Vec<u8>,
+	/// Registration of the same password.
+	registration: RegistrationRequest,
+}
+
+impl Debug for MigrationRequest {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("MigrationRequest")
+			.field("registration", &self.registration)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Holds the state of a migration on the server. See
+/// [`migrate()`](Self::migrate).
+#[must_use = "Does nothing if not `finish`ed"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ServerMigration {
+	/// Underlying registration process.
+	registration: ServerRegistration,
+}
+
+impl ServerMigration {
+	/// Returns the [`Config`] associated with this [`ServerMigration`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.registration.config()
+	}
+
+	/// Starts the migration of a user stored with `legacy`. The password of
+	/// the [`MigrationRequest`] is [`open`](SecureChannel::open)ed with
+	/// `channel` and verified against `legacy` before the registration is
+	/// started. The returned [`RegistrationResponse`] has to be sent back to
+	/// the client. See [`ClientMigration::finish()`].
+	///
+	/// # Errors
+	/// - [`Error::Sequence`] or [`Error::Channel`] if the password couldn't be
+	///   opened with `channel`
+	/// - [`Error::Credentials`] if the password doesn't match `legacy`
+	/// - [`Error::LegacyHash`] if the parameters of `legacy` are invalid
+	/// - [`Error::Config`] if [`ServerConfig`] and [`MigrationRequest`] were
+	///   not created with the same [`Config`]
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn migrate(
+		config: &ServerConfig,
+		legacy: &LegacyHash,
+		request: MigrationRequest,
+		channel: &mut SecureChannel,
+	) -> Result<(Self, RegistrationResponse)> {
+		let password = Zeroizing::new(channel.open(&request.password)?);
+		legacy.verify(&*password)?;
+		let (registration, response) =
+			ServerRegistration::register_unchecked(config, request.registration)?;
+
+		Ok((Self { registration }, response))
+	}
+
+	/// Finishes the migration. The returned [`ServerFile`] has to replace the
+	/// [`LegacyHash`] in the same transaction, the [`LegacyHash`] must not be
+	/// accepted afterwards.
+	///
+	/// # Errors
+	/// [`Error::Config`] if [`ServerMigration`] and
+	/// [`RegistrationFinalization`] were not created with the same [`Config`].
+	pub fn finish(self, finalization: RegistrationFinalization) -> Result<ServerFile> {
+		self.registration.finish(finalization)
+	}
+}
+
+#[test]
+fn legacy_hash() -> anyhow::Result<()> {
+	const HASHES: &[(&str, LegacyAlgorithm)] = &[
+		(
+			concat!(
+				"$argon2id$v=19$m=256,t=1,p=1$c29tZXNhbHRzb21lc2FsdA$",
+				"hn7+SzhLFWN5L2vw5todiDT80rAFnVmYwlI26PSf7pM"
+			),
+			LegacyAlgorithm::Argon2,
+		),
+		(
+			concat!(
+				"$argon2i$v=19$m=256,t=1,p=1$c29tZXNhbHRzb21lc2FsdA$",
+				"mX88KMCKhOUjtTdIsU0CQdd3uRJXwxtcUVLGTOWOrmI"
+			),
+			LegacyAlgorithm::Argon2,
+		),
+		(
+			concat!(
+				"$argon2d$v=19$m=256,t=1,p=1$c29tZXNhbHRzb21lc2FsdA$",
+				"70z+Xybs/0MO/DT+FNb1xh38YraQLZBuoFmcCypYC7U"
+			),
+			LegacyAlgorithm::Argon2,
+		),
+		#[cfg(feature = "bcrypt")]
+		(
+			"$2a$04$KBCwKxOzLha2MUDgW0PjXehyC7kcbJmICs4eWpZZOlh/QJzfSPPHe",
+			LegacyAlgorithm::Bcrypt,
+		),
+		#[cfg(feature = "bcrypt")]
+		(
+			"$2b$04$KBCwKxOzLha2MUDgW0PjXehyC7kcbJmICs4eWpZZOlh/QJzfSPPHe",
+			LegacyAlgorithm::Bcrypt,
+		),
+		#[cfg(feature = "bcrypt")]
+		(
+			"$2y$04$KBCwKxOzLha2MUDgW0PjXehyC7kcbJmICs4eWpZZOlh/QJzfSPPHe",
+			LegacyAlgorithm::Bcrypt,
+		),
+		#[cfg(feature = "pbkdf2")]
+		(
+			concat!(
+				"$pbkdf2-sha256$i=1000,l=32$c29tZXNhbHRzb21lc2FsdA$",
+				"s5LQUeAEZUMuFVrnmF3OMNPXs3QWnF8SO/5BXmCj6QQ"
+			),
+			LegacyAlgorithm::Pbkdf2,
+		),
+		#[cfg(feature = "pbkdf2")]
+		(
+			concat!(
+				"$pbkdf2-sha512$i=1000,l=64$c29tZXNhbHRzb21lc2FsdA$",
+				"a5wgoWFIPKuJOEszqMEKfpxJMYmocERsHXaC6Cvdkda",
+				"TNCkO6JxcKuDoNYXi3iPDLnjgJCAVtWtsfscGAZC0PQ"
+			),
+			LegacyAlgorithm::Pbkdf2,
+		),
+	];
+
+	for (hash, algorithm) in HASHES {
+		let legacy = LegacyHash::new(*hash)?;
+		assert_eq!(legacy.algorithm(), *algorithm);
+		assert_eq!(legacy.as_str(), *hash);
+		legacy.verify("password")?;
+		assert_eq!(legacy.verify("wrong"), Err(Error::Credentials), "{}", hash);
+	}
+
+	// OpenBSD test vector
+	#[cfg(feature = "bcrypt")]
+	LegacyHash::new("$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW")?
+		.verify("U*U")?;
+
+	// malformed or unsupported
+	for hash in [
+		"",
+		"password",
+		"$2b$04$invalid",
+		"$argon2id$v=19$m=256,t=1,p=1",
+		"$scrypt$ln=4,r=8,p=1$c29tZXNhbHQ$aGFzaA",
+		"$md5$c29tZXNhbHQ$aGFzaA",
+	] {
+		assert_eq!(LegacyHash::new(hash), Err(Error::LegacyHash), "{}", hash);
+	}
+
+	Ok(())
+}
+
+#[test]
+fn migration() -> anyhow::Result<()> {
+	use arrayvec::ArrayVec;
+
+	use crate::{ClientLogin, PasswordPolicy, ServerLogin, SessionKey};
+
+	const PASSWORD: &[u8] = b"password";
+	// legacy passwords aren't checked against the policy
+	let server_config = ServerConfig::default().with_policy(PasswordPolicy::new(12, None)?);
+	let client_config = ClientConfig::default();
+	let legacy = LegacyHash::new(concat!(
+		"$argon2id$v=19$m=256,t=1,p=1$c29tZXNhbHRzb21lc2FsdA$",
+		"hn7+SzhLFWN5L2vw5todiDT80rAFnVmYwlI26PSf7pM"
+	))?;
+	let session_key = SessionKey::new(ArrayVec::from([1; 64]));
+	let mut client_channel = SecureChannel::client(&session_key);
+	let mut server_channel = SecureChannel::server(&session_key);
+
+	// sealed with another session
+	let other_key = SessionKey::new(ArrayVec::from([2; 64]));
+	let (_, request) = ClientMigration::migrate(
+		client_config,
+		PASSWORD,
+		&mut SecureChannel::client(&other_key),
+	)?;
+	assert_eq!(
+		ServerMigration::migrate(&server_config, &legacy, request, &mut server_channel),
+		Err(Error::Channel)
+	);
+
+	// wrong password
+	let (_, request) = ClientMigration::migrate(client_config, b"wrong", &mut client_channel)?;
+	assert_eq!(
+		ServerMigration::migrate(&server_config, &legacy, request, &mut server_channel),
+		Err(Error::Credentials)
+	);
+
+	// migration
+	let (client, request) = ClientMigration::migrate(client_config, PASSWORD, &mut client_channel)?;
+	let request: MigrationRequest = bincode::deserialize(&bincode::serialize(&request)?)?;
+	assert!(!format!("{:?}", request).contains("password"));
+	assert!(!request
+		.password
+		.windows(PASSWORD.len())
+		.any(|window| window == PASSWORD));
+	let (server, response) = ServerMigration::migrate(
+		&server_config,
+		&legacy,
+		request.clone(),
+		&mut server_channel,
+	)?;
+	// replayed requests are rejected
+	assert_eq!(
+		ServerMigration::migrate(&server_config, &legacy, request, &mut server_channel),
+		Err(Error::Sequence)
+	);
+	let server: ServerMigration = bincode::deserialize(&bincode::serialize(&server)?)?;
+	let (client_file, finalization, export_key) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	// login with the migrated password
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, login_export_key, _) = client.finish(response)?;
+	server.finish(finalization)?;
+	assert_eq!(export_key, login_export_key);
+
+	// different config
+	let (_, request) = ClientMigration::migrate(client_config, PASSWORD, &mut client_channel)?;
+	let config = Config::default().with_normalization(crate::Normalization::Nfkc);
+	assert_eq!(
+		ServerMigration::migrate(
+			&ServerConfig::new(config),
+			&legacy,
+			request,
+			&mut server_channel
+		),
+		Err(Error::Config)
+	);
+
+	Ok(())
+}
@@ -0,0 +1,269 @@
+//! Oblivious breached-password check, see [`BreachServer`].
+
+use std::{
+	collections::HashMap,
+	convert::TryInto,
+	fmt::{self, Debug, Formatter},
+};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{
+	breach,
+	oprf::{self, ELEMENT_SIZE},
+	Config, Error, Result, ServerConfig,
+};
+
+/// HKDF info used to derive the OPRF key and domain separation of the OPRF.
+const INFO: &[u8; 25] = b"custodian-password breach";
+/// Number of bytes of the SHA-1 hash revealed to the server to select a
+/// bucket.
+const PREFIX_SIZE: usize = 2;
+
+/// Server holding OPRF outputs of breached passwords, bucketed by a short
+/// SHA-1 prefix.
+///
+/// The client blinds its password with [`BreachClient::check()`], the server
+/// evaluates the OPRF with a key dedicated to this [`ServerConfig`] and
+/// returns the outputs of the requested bucket, the client unblinds the result
+/// and looks for it in the bucket. The server only learns the prefix, the
+/// client only learns the outputs of a single bucket, which it can't test
+/// passwords against without the server.
+///
+/// [`BreachServer`] can be used in-process, e.g. in tests, or behind any
+/// transport by sending [`BreachRequest`] and [`BreachResponse`].
+#[derive(Clone)]
+pub struct BreachServer {
+	/// OPRF key.
+	key: oprf::Key,
+	/// OPRF outputs of breached passwords by SHA-1 prefix.
+	buckets: HashMap<[u8; PREFIX_SIZE], Vec<[u8; 32]>>,
+}
+
+impl Debug for BreachServer {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("BreachServer")
+			.field("buckets", &self.buckets.len())
+			.finish_non_exhaustive()
+	}
+}
+
+impl BreachServer {
+	/// Creates an empty [`BreachServer`] with an OPRF key derived from
+	/// `config`.
+	#[must_use]
+	pub fn new(config: &ServerConfig) -> Self {
+		let key = config.derive_key(INFO);
+
+		Self {
+			key: oprf::Key::derive(&key[..]),
+			buckets: HashMap::new(),
+		}
+	}
+
+	/// Inserts a breached `password`, after applying the
+	/// [`Normalization`](crate::Normalization) of `config`. Pass the same
+	/// [`Config`] the clients pass to [`BreachClient::check()`].
+	///
+	/// # Errors
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::Oprf`] if the OPRF failed
+	pub fn insert<P: AsRef<[u8]>>(&mut self, config: Config, password: P) -> Result<()> {
+		let password = config.normalization().normalize(password.as_ref())?;
+		self.insert_hash(&breach::hash(&password))
+	}
+
+	/// Inserts the SHA-1 `hash` of a breached password, as found in "Pwned
+	/// Passwords" dumps.
+	///
+	/// # Errors
+	/// [`Error::Oprf`] if the OPRF failed.
+	pub fn insert_hash(&mut self, hash: &[u8; 20]) -> Result<()> {
+		let (client, blinded) = oprf::Client::blind(hash).ok_or(Error::Oprf)?;
+		let evaluated = self.key.evaluate(&blinded, INFO).ok_or(Error::Oprf)?;
+		let output = output(&client, &evaluated).ok_or(Error::Oprf)?;
+
+		self.buckets.entry(prefix(hash)).or_default().push(output);
+
+		Ok(())
+	}
+
+	/// Evaluates the OPRF on the blinded password of `request`. The returned
+	/// [`BreachResponse`] has to be sent back to the client. See
+	/// [`BreachClient::finish()`].
+	///
+	/// # Errors
+	/// [`Error::BreachRequest`] if the blinded password is invalid.
+	pub fn evaluate(&self, request: &BreachRequest) -> Result<BreachResponse> {
+		Ok(BreachResponse {
+			evaluated: self
+				.key
+				.evaluate(&request.blinded, INFO)
+				.ok_or(Error::BreachRequest)?,
+			bucket: self
+				.buckets
+				.get(&request.prefix)
+				.cloned()
+				.unwrap_or_default(),
+		})
+	}
+}
+
+/// Holds the state of a breach check. See [`check()`](Self::check).
+#[must_use = "Use `finish()` to complete the breach check"]
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct BreachClient {
+	/// OPRF state of the blinded password.
+	client: oprf::Client,
+}
+
+impl Debug for BreachClient {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("BreachClient").finish_non_exhaustive()
+	}
+}
+
+impl BreachClient {
+	/// Starts the breach check of `password`. The returned [`BreachRequest`]
+	/// has to be sent to the server. See [`BreachServer::evaluate()`].
+	///
+	/// Pass the same [`Config`] and password given to
+	/// [`ClientRegistration::register()`](crate::ClientRegistration::register),
+	/// the password is checked after applying the
+	/// [`Normalization`](crate::Normalization) of `config`.
+	///
+	/// # Errors
+	/// - [`Error::Password`] if `password` is rejected by the
+	///   [`Normalization`](crate::Normalization)
+	/// - [`Error::Oprf`] if the OPRF failed
+	pub fn check<P: AsRef<[u8]>>(config: Config, password: P) -> Result<(Self, BreachRequest)> {
+		let password = config.normalization().normalize(password.as_ref())?;
+		let hash = breach::hash(&password);
+		let (client, blinded) = oprf::Client::blind(&hash).ok_or(Error::Oprf)?;
+
+		Ok((Self { client }, BreachRequest {
+			prefix: prefix(&hash),
+			blinded,
+		}))
+	}
+
+	/// Finishes the breach check.
+	///
+	/// # Errors
+	/// - [`Error::PasswordBreached`] if the password was breached
+	/// - [`Error::BreachResponse`] if the evaluated password is invalid
+	#[allow(clippy::needless_pass_by_value)]
+	pub fn finish(self, response: BreachResponse) -> Result<()> {
+		let output = output(&self.client, &response.evaluated).ok_or(Error::BreachResponse)?;
+
+		if response.bucket.contains(&output) {
+			Err(Error::PasswordBreached)
+		} else {
+			Ok(())
+		}
+	}
+}
+
+/// Blinded password sent by the client. See [`BreachServer::evaluate()`].
+#[must_use = "Does nothing if not sent to the server"]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BreachRequest {
+	/// SHA-1 prefix of the password.
+	prefix: [u8; PREFIX_SIZE],
+	/// Blinded password.
+	blinded: [u8; ELEMENT_SIZE],
+}
+
+/// Evaluated password and bucket sent by the server. See
+/// [`BreachClient::finish()`].
+#[must_use = "Does nothing if not sent to the client"]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct BreachResponse {
+	/// Evaluated blinded password.
+	evaluated: [u8; ELEMENT_SIZE],
+	/// OPRF outputs of breached passwords with the same prefix.
+	bucket: Vec<[u8; 32]>,
+}
+
+/// Returns the bucket of `hash`.
+const fn prefix(hash: &[u8; 20]) -> [u8; PREFIX_SIZE] {
+	[hash[0], hash[1]]
+}
+
+/// Returns the OPRF output of `client` from the `evaluated` password,
+/// truncated to the size stored in buckets.
+fn output(client: &oprf::Client, evaluated: &[u8; ELEMENT_SIZE]) -> Option<[u8; 32]> {
+	client.finalize(evaluated, INFO)?[..32].try_into().ok()
+}
+
+#[test]
+fn oblivious() -> anyhow::Result<()> {
+	use crate::Normalization;
+
+	let config = ServerConfig::default();
+	let mut server = BreachServer::new(&config);
+	server.insert(config.config(), "password")?;
+	server.insert_hash(&breach::hash(b"hunter2"))?;
+
+	// breached
+	let (client, request) = BreachClient::check(config.config(), "password")?;
+	let request: BreachRequest = bincode::deserialize(&bincode::serialize(&request)?)?;
+	let response = server.evaluate(&request)?;
+	let response: BreachResponse = bincode::deserialize(&bincode::serialize(&response)?)?;
+	let client: BreachClient = bincode::deserialize(&bincode::serialize(&client)?)?;
+	assert_eq!(client.finish(response), Err(Error::PasswordBreached));
+
+	let (client, request) = BreachClient::check(config.config(), "hunter2")?;
+	assert_eq!(
+		client.finish(server.evaluate(&request)?),
+		Err(Error::PasswordBreached)
+	);
+
+	// normalized before hashing
+	let nfkc = config.config().with_normalization(Normalization::Nfkc);
+	let (client, request) = BreachClient::check(config.config(), "\u{ff50}assword")?;
+	client.finish(server.evaluate(&request)?)?;
+	let (client, request) = BreachClient::check(nfkc, "\u{ff50}assword")?;
+	assert_eq!(
+		client.finish(server.evaluate(&request)?),
+		Err(Error::PasswordBreached)
+	);
+	server.insert(nfkc, "\u{ff48}unter3")?;
+	let (client, request) = BreachClient::check(nfkc, "hunter3")?;
+	assert_eq!(
+		client.finish(server.evaluate(&request)?),
+		Err(Error::PasswordBreached)
+	);
+
+	// not breached
+	let (client, request) = BreachClient::check(config.config(), "correct horse battery staple")?;
+	client.finish(server.evaluate(&request)?)?;
+
+	// blinding hides the password
+	let (_, first) = BreachClient::check(config.config(), "password")?;
+	let (_, second) = BreachClient::check(config.config(), "password")?;
+	assert_eq!(first.prefix, second.prefix);
+	assert_ne!(first.blinded, second.blinded);
+
+	// different key
+	let (client, request) = BreachClient::check(config.config(), "password")?;
+	client.finish(BreachServer::new(&ServerConfig::default()).evaluate(&request)?)?;
+
+	// invalid points
+	let mut invalid = request;
+	invalid.blinded = [0xff; 32];
+	assert_eq!(server.evaluate(&invalid), Err(Error::BreachRequest));
+
+	let (client, request) = BreachClient::check(config.config(), "password")?;
+	let mut response = server.evaluate(&request)?;
+	response.evaluated = [0xff; 32];
+	assert_eq!(client.finish(response), Err(Error::BreachResponse));
+
+	let (client, _) = BreachClient::check(config.config(), "password")?;
+	assert_eq!(format!("{:?}", client), "BreachClient { .. }");
+
+	Ok(())
+}
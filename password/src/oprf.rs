@@ -0,0 +1,136 @@
+//! Oblivious pseudorandom function shared by
+//! [`BreachServer`](crate::BreachServer) and
+//! [`HardeningServer`](crate::HardeningServer), see [`Key`] and [`Client`].
+//! Wraps the non-verifiable mode of [voprf] over Ristretto255 and SHA-512.
+
+use std::convert::TryInto;
+
+use curve25519_dalek::{
+	ristretto::{CompressedRistretto, RistrettoPoint},
+	scalar::Scalar,
+};
+use generic_array::{typenum::U64, GenericArray};
+use opaque_ke::rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use voprf::{
+	BlindedElement, EvaluationElement, Metadata, NonVerifiableClient, NonVerifiableServer,
+};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto;
+
+/// Size of a serialized group element.
+pub(crate) const ELEMENT_SIZE: usize = 32;
+
+/// OPRF key of a server.
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub(crate) struct Key([u8; 32]);
+
+crypto::impl_secret!(Key);
+
+impl Key {
+	/// Generates a random [`Key`].
+	pub(crate) fn random() -> Self {
+		let random = Zeroizing::new(crypto::random::<64>());
+		Self(Scalar::from_bytes_mod_order_wide(&random).to_bytes())
+	}
+
+	/// Derives a [`Key`] from `ikm`.
+	pub(crate) fn derive(ikm: &[u8]) -> Self {
+		Self(Scalar::from_hash(Sha512::new().chain(ikm)).to_bytes())
+	}
+
+	/// Evaluates the OPRF on an element blinded by [`Client::blind()`].
+	/// `info` has to match the one passed to [`Client::finalize()`].
+	///
+	/// Returns [`None`] if `blinded` is invalid.
+	pub(crate) fn evaluate(
+		&self,
+		blinded: &[u8; ELEMENT_SIZE],
+		info: &[u8],
+	) -> Option<[u8; ELEMENT_SIZE]> {
+		let server = NonVerifiableServer::<RistrettoPoint, Sha512>::new_with_key(&self.0).ok()?;
+		let blinded = BlindedElement::deserialize(blinded).ok()?;
+		let evaluated = server.evaluate(&blinded, &Metadata(info.to_vec())).ok()?;
+
+		evaluated.message.serialize().as_slice().try_into().ok()
+	}
+
+	/// Returns the ratio between this [`Key`] and the `old` one, which moves
+	/// elements unblinded with [`Client::unblind()`] from the `old` key to
+	/// this one, see [`update()`].
+	pub(crate) fn delta(&self, old: &Self) -> Zeroizing<[u8; 32]> {
+		Zeroizing::new((self.scalar() * old.scalar().invert()).to_bytes())
+	}
+
+	/// Returns the [`Scalar`] of this [`Key`].
+	fn scalar(&self) -> Scalar {
+		Scalar::from_bytes_mod_order(self.0)
+	}
+}
+
+/// Client state of an OPRF evaluation, holds the input and its blind.
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub(crate) struct Client(Vec<u8>);
+
+impl Client {
+	/// Blinds `input`. The returned element has to be evaluated by the
+	/// server, see [`Key::evaluate()`].
+	///
+	/// Returns [`None`] if `input` couldn't be hashed to the group.
+	pub(crate) fn blind(input: &[u8]) -> Option<(Self, [u8; ELEMENT_SIZE])> {
+		let result =
+			NonVerifiableClient::<RistrettoPoint, Sha512>::blind(input.to_vec(), &mut OsRng)
+				.ok()?;
+		let blinded = result.message.serialize().as_slice().try_into().ok()?;
+
+		Some((Self(result.state.serialize()), blinded))
+	}
+
+	/// Returns the OPRF output from the element `evaluated` by the server.
+	///
+	/// Returns [`None`] if `evaluated` is invalid.
+	pub(crate) fn finalize(
+		&self,
+		evaluated: &[u8; ELEMENT_SIZE],
+		info: &[u8],
+	) -> Option<GenericArray<u8, U64>> {
+		let state = NonVerifiableClient::<RistrettoPoint, Sha512>::deserialize(&self.0).ok()?;
+		let evaluated = EvaluationElement::deserialize(evaluated).ok()?;
+
+		state.finalize(&evaluated, &Metadata(info.to_vec())).ok()
+	}
+
+	/// Removes the blind from the element `evaluated` by the server. Unlike
+	/// the output of [`finalize()`](Self::finalize) the result can be moved to
+	/// a new [`Key`] with [`update()`].
+	///
+	/// Returns [`None`] if `evaluated` is invalid.
+	pub(crate) fn unblind(&self, evaluated: &[u8; ELEMENT_SIZE]) -> Option<[u8; ELEMENT_SIZE]> {
+		let state = NonVerifiableClient::<RistrettoPoint, Sha512>::deserialize(&self.0).ok()?;
+		let evaluated = EvaluationElement::<RistrettoPoint, Sha512>::deserialize(evaluated).ok()?;
+
+		Some(
+			(evaluated.value() * state.get_blind().invert())
+				.compress()
+				.to_bytes(),
+		)
+	}
+}
+
+/// Moves an `element` unblinded with [`Client::unblind()`] to a new [`Key`]
+/// with the `delta` returned by [`Key::delta()`].
+///
+/// Returns [`None`] if `element` is invalid.
+pub(crate) fn update(element: &[u8; ELEMENT_SIZE], delta: &[u8; 32]) -> Option<[u8; ELEMENT_SIZE]> {
+	let element = CompressedRistretto(*element).decompress()?;
+
+	Some(
+		(element * Scalar::from_bytes_mod_order(*delta))
+			.compress()
+			.to_bytes(),
+	)
+}
@@ -0,0 +1,50 @@
+//! See [`Password`].
+
+use zeroize::Zeroize;
+
+use crate::crypto;
+
+/// Password that is zeroized on drop. Can be passed to
+/// [`ClientRegistration::register()`](crate::ClientRegistration::register) and
+/// [`ClientLogin::login()`](crate::ClientLogin::login), which apply the
+/// [`Normalization`](crate::Normalization) of the [`Config`](crate::Config).
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct Password(String);
+
+impl Password {
+	/// Creates a [`Password`] from a [`String`].
+	#[must_use]
+	pub const fn new(password: String) -> Self {
+		Self(password)
+	}
+
+	/// Returns the [`str`] of this [`Password`].
+	#[must_use]
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+}
+
+crypto::impl_secret!(Password, |this, other| this
+	.0
+	.as_bytes()
+	.ct_eq(other.0.as_bytes()));
+
+impl From<String> for Password {
+	fn from(password: String) -> Self {
+		Self::new(password)
+	}
+}
+
+impl From<&str> for Password {
+	fn from(password: &str) -> Self {
+		Self::new(password.to_owned())
+	}
+}
+
+impl AsRef<[u8]> for Password {
+	fn as_ref(&self) -> &[u8] {
+		self.0.as_bytes()
+	}
+}
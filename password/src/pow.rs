@@ -0,0 +1,380 @@
+//! Proof-of-work challenge to throttle registration and login attempts, see
+//! [`Challenge`].
+
+use std::{
+	convert::TryFrom,
+	time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+	crypto,
+	ledger::{self, TokenId},
+	Error, LoginRequest, LoginResponse, RegistrationRequest, RegistrationResponse, Result,
+	ServerConfig, ServerFile, ServerLogin, ServerRegistration, TokenLedger,
+};
+
+/// Domain separation of the HKDF info used to derive the key authenticating
+/// [`Challenge`]s and of the puzzle hash.
+const INFO: &[u8] = b"custodian-password pow";
+
+/// Number of leading zero bits a [`Solution`] has to produce.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(try_from = "u8")]
+pub struct Difficulty(u8);
+
+impl TryFrom<u8> for Difficulty {
+	type Error = Error;
+
+	fn try_from(bits: u8) -> Result<Self> {
+		Self::new(bits)
+	}
+}
+
+impl Difficulty {
+	/// Highest supported [`Difficulty`], takes about 2^32 hashes to solve.
+	pub const MAX: Self = Self(32);
+
+	/// Creates a [`Difficulty`] requiring `bits` leading zero bits. Every
+	/// additional bit doubles the expected work of the client.
+	///
+	/// # Errors
+	/// [`Error::Difficulty`] if `bits` is higher than [`Difficulty::MAX`].
+	pub const fn new(bits: u8) -> Result<Self> {
+		if bits > Self::MAX.0 {
+			Err(Error::Difficulty)
+		} else {
+			Ok(Self(bits))
+		}
+	}
+
+	/// Returns the number of leading zero bits required.
+	#[must_use]
+	pub const fn bits(self) -> u8 {
+		self.0
+	}
+
+	/// Scales linearly between `min` and `max` according to `load`, the
+	/// current load of the server in percent. A `load` above 100 is treated as
+	/// 100.
+	#[must_use]
+	#[allow(clippy::integer_arithmetic)]
+	pub fn scale(min: Self, max: Self, load: u8) -> Self {
+		let (min, max) = (min.0.min(max.0), min.0.max(max.0));
+		let load = u16::from(load.min(100));
+		let bits = u16::from(max - min) * load / 100;
+
+		Self(min + u8::try_from(bits).unwrap_or(max - min))
+	}
+}
+
+/// Stateless puzzle issued by the server, the client has to
+/// [`solve()`](Self::solve) it before sending a [`RegistrationRequest`] or
+/// [`LoginRequest`].
+///
+/// The [`Solution`] is bound to the authenticated [`Challenge`] and verified
+/// without any server-side state, it can be reused until the [`Challenge`]
+/// expires, so keep the validity short. Servers that need single-use
+/// [`Challenge`]s can additionally [`spend()`](Solution::spend) them in a
+/// [`TokenLedger`], at the cost of storing every [`Challenge`] until it
+/// expires.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Challenge {
+	/// Identifies this challenge in a [`TokenLedger`].
+	id: TokenId,
+	/// Random nonce of the server.
+	nonce: [u8; 32],
+	/// Required [`Difficulty`].
+	difficulty: Difficulty,
+	/// Expiry in seconds since the Unix epoch.
+	expires: u64,
+	/// MAC over all other fields.
+	tag: [u8; 32],
+}
+
+impl Challenge {
+	/// Issues a new [`Challenge`] of `difficulty`, valid for `validity`.
+	#[must_use]
+	pub fn issue(config: &ServerConfig, difficulty: Difficulty, validity: Duration) -> Self {
+		let id = TokenId::new();
+		let nonce = crypto::random();
+		let expires = ledger::expiry(validity);
+		let tag = crypto::mac(&config.derive_key(INFO), &[
+			id.as_bytes(),
+			&nonce,
+			&[difficulty.0],
+			&expires.to_be_bytes(),
+		]);
+
+		Self {
+			id,
+			nonce,
+			difficulty,
+			expires,
+			tag,
+		}
+	}
+
+	/// Returns the [`TokenId`] of this [`Challenge`], use it to
+	/// [revoke](TokenLedger::revoke) it.
+	#[must_use]
+	pub const fn id(&self) -> TokenId {
+		self.id
+	}
+
+	/// Returns the [`Difficulty`] of this [`Challenge`].
+	#[must_use]
+	pub const fn difficulty(&self) -> Difficulty {
+		self.difficulty
+	}
+
+	/// Returns the expiry of this [`Challenge`].
+	#[must_use]
+	pub fn expires(&self) -> SystemTime {
+		ledger::from_timestamp(self.expires)
+	}
+
+	/// Solves this [`Challenge`]. The returned [`Solution`] has to be sent to
+	/// the server alongside the [`RegistrationRequest`] or [`LoginRequest`].
+	pub fn solve(&self) -> Solution {
+		Solution {
+			counter: solve(&self.tag, self.difficulty),
+			challenge: *self,
+		}
+	}
+}
+
+/// Solution of a [`Challenge`]. See
+/// [`ServerRegistration::register_challenged()`] and
+/// [`ServerLogin::login_challenged()`].
+#[must_use = "Does nothing if not sent to the server"]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Solution {
+	/// Solved [`Challenge`].
+	challenge: Challenge,
+	/// Counter producing the required leading zero bits.
+	counter: u64,
+}
+
+impl Solution {
+	/// Verifies this [`Solution`] without any server-side state.
+	///
+	/// # Errors
+	/// - [`Error::Challenge`] if the [`Challenge`] wasn't issued by this
+	///   [`ServerConfig`]
+	/// - [`Error::ChallengeExpired`] if the [`Challenge`] expired
+	/// - [`Error::Solution`] if the [`Challenge`] wasn't solved
+	pub fn verify(&self, config: &ServerConfig) -> Result<()> {
+		let challenge = &self.challenge;
+
+		if !crypto::verify(
+			&config.derive_key(INFO),
+			&[
+				challenge.id.as_bytes(),
+				&challenge.nonce,
+				&[challenge.difficulty.0],
+				&challenge.expires.to_be_bytes(),
+			],
+			&challenge.tag,
+		) {
+			return Err(Error::Challenge);
+		}
+
+		if ledger::now() >= challenge.expires {
+			return Err(Error::ChallengeExpired);
+		}
+
+		if leading_zeros(&hash(&challenge.tag, self.counter)) < u32::from(challenge.difficulty.0) {
+			return Err(Error::Solution);
+		}
+
+		Ok(())
+	}
+
+	/// Marks the [`Challenge`] as used in `ledger`, making this [`Solution`]
+	/// single-use. Only call it after [`verify()`](Self::verify) succeeded.
+	///
+	/// # Errors
+	/// [`Error::ChallengeUsed`] if the [`Challenge`] was already used or
+	/// revoked.
+	pub fn spend(&self, ledger: &mut TokenLedger) -> Result<()> {
+		if ledger.spend(self.challenge.id, self.challenge.expires) {
+			Ok(())
+		} else {
+			Err(Error::ChallengeUsed)
+		}
+	}
+}
+
+impl ServerRegistration {
+	/// Starts the registration process like [`register()`](Self::register),
+	/// but requires a valid [`Solution`], see [`Solution::verify()`].
+	///
+	/// # Errors
+	/// - [`Error::Challenge`] if the [`Challenge`] wasn't issued by this
+	///   [`ServerConfig`]
+	/// - [`Error::ChallengeExpired`] if the [`Challenge`] expired
+	/// - [`Error::Solution`] if the [`Challenge`] wasn't solved
+	/// - [`Error::Config`] if [`ServerConfig`] and [`RegistrationRequest`] were
+	///   not created with the same [`Config`](crate::Config)
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn register_challenged(
+		config: &ServerConfig,
+		request: RegistrationRequest,
+		solution: &Solution,
+	) -> Result<(Self, RegistrationResponse)> {
+		solution.verify(config)?;
+
+		Self::register(config, request)
+	}
+}
+
+impl ServerLogin {
+	/// Starts the login process like [`login()`](Self::login), but requires a
+	/// valid [`Solution`], see [`Solution::verify()`].
+	///
+	/// # Errors
+	/// - [`Error::Challenge`] if the [`Challenge`] wasn't issued by this
+	///   [`ServerConfig`]
+	/// - [`Error::ChallengeExpired`] if the [`Challenge`] expired
+	/// - [`Error::Solution`] if the [`Challenge`] wasn't solved
+	/// - [`Error::Config`] if [`ServerConfig`], [`ServerFile`] and
+	///   [`LoginRequest`] were not created with the same
+	///   [`Config`](crate::Config)
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn login_challenged(
+		config: &ServerConfig,
+		file: Option<ServerFile>,
+		request: LoginRequest,
+		solution: &Solution,
+	) -> Result<(Self, LoginResponse)> {
+		solution.verify(config)?;
+
+		Self::login(config, file, request)
+	}
+}
+
+/// Finds the first counter producing `difficulty` leading zero bits.
+fn solve(tag: &[u8; 32], difficulty: Difficulty) -> u64 {
+	(0..=u64::MAX)
+		.find(|counter| leading_zeros(&hash(tag, *counter)) >= u32::from(difficulty.0))
+		.expect("no solution found")
+}
+
+/// Hashes the `tag` of a [`Challenge`] and `counter`.
+fn hash(tag: &[u8; 32], counter: u64) -> [u8; 32] {
+	Sha256::new()
+		.chain(INFO)
+		.chain(tag)
+		.chain(counter.to_be_bytes())
+		.finalize()
+		.into()
+}
+
+/// Counts the leading zero bits of `hash`.
+#[allow(clippy::integer_arithmetic)]
+fn leading_zeros(hash: &[u8]) -> u32 {
+	let mut zeros = 0;
+
+	for byte in hash {
+		zeros += byte.leading_zeros();
+
+		if *byte != 0 {
+			break;
+		}
+	}
+
+	zeros
+}
+
+#[test]
+fn difficulty() -> anyhow::Result<()> {
+	let min = Difficulty::new(8)?;
+	let max = Difficulty::new(24)?;
+
+	assert_eq!(Difficulty::scale(min, max, 0), min);
+	assert_eq!(Difficulty::scale(min, max, 50), Difficulty::new(16)?);
+	assert_eq!(Difficulty::scale(min, max, 100), max);
+	assert_eq!(Difficulty::scale(min, max, u8::MAX), max);
+	assert_eq!(Difficulty::scale(max, min, 100), max);
+	assert_eq!(Difficulty::new(33), Err(Error::Difficulty));
+
+	let difficulty: Difficulty = bincode::deserialize(&bincode::serialize(&max)?)?;
+	assert_eq!(difficulty, max);
+	bincode::deserialize::<Difficulty>(&bincode::serialize(&33_u8)?).unwrap_err();
+
+	Ok(())
+}
+
+#[test]
+fn pow() -> anyhow::Result<()> {
+	use crate::{ClientConfig, ClientLogin, ClientRegistration};
+
+	const PASSWORD: &[u8] = b"password";
+	const VALIDITY: Duration = Duration::from_secs(60);
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+	let difficulty = Difficulty::new(8)?;
+
+	// registration process
+	let challenge = Challenge::issue(&server_config, difficulty, VALIDITY);
+	let challenge: Challenge = bincode::deserialize(&bincode::serialize(&challenge)?)?;
+	let solution = challenge.solve();
+	let solution: Solution = bincode::deserialize(&bincode::serialize(&solution)?)?;
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) =
+		ServerRegistration::register_challenged(&server_config, request, &solution)?;
+	let (client_file, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	// login process
+	let solution = Challenge::issue(&server_config, difficulty, VALIDITY).solve();
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) =
+		ServerLogin::login_challenged(&server_config, Some(server_file), request, &solution)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	server.finish(finalization)?;
+
+	// stateless until spent
+	solution.verify(&server_config)?;
+	let mut ledger = TokenLedger::new();
+	solution.spend(&mut ledger)?;
+	assert_eq!(solution.spend(&mut ledger), Err(Error::ChallengeUsed));
+
+	// revoked
+	let revoked = Challenge::issue(&server_config, difficulty, VALIDITY);
+	ledger.revoke(revoked.id(), Some(revoked.expires()));
+	assert_eq!(
+		revoked.solve().spend(&mut ledger),
+		Err(Error::ChallengeUsed)
+	);
+
+	// unsolved
+	let mut unsolved = solution;
+	unsolved.counter = (0..=u64::MAX)
+		.find(|counter| leading_zeros(&hash(&solution.challenge.tag, *counter)) < 8)
+		.expect("no counter found");
+	assert_eq!(unsolved.verify(&server_config), Err(Error::Solution));
+
+	// tampered difficulty
+	let mut tampered = Challenge::issue(&server_config, difficulty, VALIDITY);
+	tampered.difficulty = Difficulty::new(0)?;
+	assert_eq!(
+		tampered.solve().verify(&server_config),
+		Err(Error::Challenge)
+	);
+
+	// different server
+	assert_eq!(
+		solution.verify(&ServerConfig::default()),
+		Err(Error::Challenge)
+	);
+
+	// expired
+	let expired = Challenge::issue(&server_config, difficulty, Duration::ZERO).solve();
+	assert_eq!(expired.verify(&server_config), Err(Error::ChallengeExpired));
+
+	Ok(())
+}
@@ -0,0 +1,354 @@
+//! Step-up re-authentication for sensitive actions, see [`ServerReauth`].
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	crypto, ledger, ClientConfig, ClientFile, ClientLogin, Config, Error, ExportKey,
+	LoginFinalization, LoginRequest, LoginResponse, Result, ServerConfig, ServerFile, ServerLogin,
+};
+
+/// Domain separation of the transcript context and HKDF info used to derive
+/// the key authenticating [`ReauthProof`]s.
+const INFO: &[u8] = b"custodian-password reauth";
+
+/// Action chosen by the server, bound into the login transcript.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ReauthChallenge {
+	/// Action the user is re-authenticating for.
+	action: String,
+	/// Random nonce of the server.
+	nonce: [u8; 32],
+}
+
+impl ReauthChallenge {
+	/// Returns the action the user is re-authenticating for.
+	#[must_use]
+	pub fn action(&self) -> &str {
+		&self.action
+	}
+
+	/// Context bound into the login transcript.
+	fn context(&self) -> Vec<u8> {
+		[INFO, &encode(&self.action), &self.nonce].concat()
+	}
+}
+
+/// Send this back to the client. See [`ClientReauth::finish()`].
+#[must_use = "Does nothing if not sent to the client"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ReauthResponse {
+	/// [`ReauthChallenge`] of the server.
+	challenge: ReauthChallenge,
+	/// [`LoginResponse`] bound to the [`ReauthChallenge`].
+	response: LoginResponse,
+}
+
+impl ReauthResponse {
+	/// Returns [`Config`] used to create this [`ReauthResponse`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.response.config
+	}
+
+	/// Returns the [`ReauthChallenge`] of the server, e.g. to show the action
+	/// to the user.
+	#[must_use]
+	pub const fn challenge(&self) -> &ReauthChallenge {
+		&self.challenge
+	}
+}
+
+/// Holds the state of a re-authentication process on the client. See
+/// [`reauthenticate()`](Self::reauthenticate).
+#[must_use = "Does nothing if not `finish`ed"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClientReauth {
+	/// Underlying login process.
+	login: ClientLogin,
+}
+
+impl ClientReauth {
+	/// Returns the [`ClientConfig`] associated with this [`ClientReauth`].
+	#[must_use]
+	pub const fn config(&self) -> ClientConfig {
+		self.login.config()
+	}
+
+	/// Starts the re-authentication process. The returned [`LoginRequest`] has
+	/// to be send to the server. See [`ServerReauth::reauthenticate()`].
+	///
+	/// # Errors
+	/// See [`ClientLogin::login()`].
+	pub fn reauthenticate<P: AsRef<[u8]>>(
+		config: ClientConfig,
+		file: Option<ClientFile>,
+		password: P,
+	) -> Result<(Self, LoginRequest)> {
+		let (login, request) = ClientLogin::login(config, file, password)?;
+
+		Ok((Self { login }, request))
+	}
+
+	/// Finishes the re-authentication process for the action in
+	/// [`ReauthResponse`]. The returned [`LoginFinalization`] has to be send
+	/// back to the server. See [`ServerReauth::finish()`].
+	///
+	/// # Errors
+	/// See [`ClientLogin::finish()`].
+	pub fn finish(
+		self,
+		response: ReauthResponse,
+	) -> Result<(ClientFile, LoginFinalization, ExportKey)> {
+		let (file, finalization, export_key, _) = self
+			.login
+			.finish_with_context(response.response, &response.challenge.context())?;
+
+		Ok((file, finalization, export_key))
+	}
+}
+
+/// Holds the state of a re-authentication process on the server. See
+/// [`reauthenticate()`](Self::reauthenticate).
+#[must_use = "Does nothing if not `finish`ed"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ServerReauth {
+	/// Underlying login process.
+	login: ServerLogin,
+	/// Subject that is re-authenticating.
+	subject: String,
+	/// Action the subject is re-authenticating for.
+	action: String,
+}
+
+impl ServerReauth {
+	/// Returns the [`Config`] associated with this [`ServerReauth`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.login.config()
+	}
+
+	/// Starts the re-authentication process of `subject` for `action`. The
+	/// returned [`ReauthResponse`] has to be send back to the client. See
+	/// [`ClientReauth::finish()`].
+	///
+	/// # Errors
+	/// See [`ServerLogin::login()`].
+	pub fn reauthenticate(
+		config: &ServerConfig,
+		file: Option<ServerFile>,
+		request: LoginRequest,
+		subject: &str,
+		action: &str,
+	) -> Result<(Self, ReauthResponse)> {
+		let challenge = ReauthChallenge {
+			action: action.to_owned(),
+			nonce: crypto::random(),
+		};
+		let (login, response) =
+			ServerLogin::login_with_context(config, file, request, &challenge.context())?;
+
+		Ok((
+			Self {
+				login,
+				subject: subject.to_owned(),
+				action: action.to_owned(),
+			},
+			ReauthResponse {
+				challenge,
+				response,
+			},
+		))
+	}
+
+	/// Finishes the re-authentication process. The returned [`ReauthProof`]
+	/// can be checked with [`ReauthProof::verify()`] before performing the
+	/// action.
+	///
+	/// # Errors
+	/// - [`Error::Config`] if [`ServerConfig`] and [`ServerReauth`] were not
+	///   created with the same [`Config`]
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn finish(
+		self,
+		config: &ServerConfig,
+		finalization: LoginFinalization,
+	) -> Result<ReauthProof> {
+		if config.config() != self.login.config() {
+			return Err(Error::Config);
+		}
+
+		self.login.finish(finalization)?;
+
+		Ok(ReauthProof::new(
+			config,
+			self.subject,
+			self.action,
+			ledger::now(),
+		))
+	}
+}
+
+/// Proof that a subject re-entered their password for a specific action.
+/// Authenticated by the [`ServerConfig`], can be stored on the client. See
+/// [`verify()`](Self::verify).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ReauthProof {
+	/// Subject that re-authenticated.
+	subject: String,
+	/// Action the subject re-authenticated for.
+	action: String,
+	/// Time of re-authentication in seconds since the Unix epoch.
+	issued: u64,
+	/// MAC over all other fields.
+	tag: [u8; 32],
+}
+
+impl ReauthProof {
+	/// Creates an authenticated [`ReauthProof`].
+	fn new(config: &ServerConfig, subject: String, action: String, issued: u64) -> Self {
+		let tag = crypto::mac(&config.derive_key(INFO), &[
+			&encode(&subject),
+			&encode(&action),
+			&issued.to_be_bytes(),
+		]);
+
+		Self {
+			subject,
+			action,
+			issued,
+			tag,
+		}
+	}
+
+	/// Returns the subject that re-authenticated.
+	#[must_use]
+	pub fn subject(&self) -> &str {
+		&self.subject
+	}
+
+	/// Returns the action the subject re-authenticated for.
+	#[must_use]
+	pub fn action(&self) -> &str {
+		&self.action
+	}
+
+	/// Returns the time of re-authentication.
+	#[must_use]
+	pub fn issued(&self) -> SystemTime {
+		ledger::from_timestamp(self.issued)
+	}
+
+	/// Verifies that `subject` re-authenticated for `action` at most `max_age`
+	/// ago.
+	///
+	/// # Errors
+	/// - [`Error::ReauthProof`] if this [`ReauthProof`] wasn't issued by
+	///   [`ServerConfig`] or is for a different `subject` or `action`
+	/// - [`Error::ReauthExpired`] if the re-authentication is older than
+	///   `max_age`
+	pub fn verify(
+		&self,
+		config: &ServerConfig,
+		subject: &str,
+		action: &str,
+		max_age: Duration,
+	) -> Result<()> {
+		if !crypto::verify(
+			&config.derive_key(INFO),
+			&[
+				&encode(&self.subject),
+				&encode(&self.action),
+				&self.issued.to_be_bytes(),
+			],
+			&self.tag,
+		) || self.subject != subject
+			|| self.action != action
+		{
+			return Err(Error::ReauthProof);
+		}
+
+		if ledger::now().saturating_sub(self.issued) > max_age.as_secs() {
+			return Err(Error::ReauthExpired);
+		}
+
+		Ok(())
+	}
+}
+
+/// Length-prefixes `value`.
+fn encode(value: &str) -> Vec<u8> {
+	let length: u64 = value.len().try_into().expect("value too long");
+
+	[&length.to_be_bytes(), value.as_bytes()].concat()
+}
+
+#[test]
+fn reauth() -> anyhow::Result<()> {
+	use crate::{ClientRegistration, ServerRegistration};
+
+	const PASSWORD: &[u8] = b"password";
+	const MAX_AGE: Duration = Duration::from_secs(60);
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	// re-authentication process
+	let (client, request) =
+		ClientReauth::reauthenticate(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerReauth::reauthenticate(
+		&server_config,
+		Some(server_file.clone()),
+		request,
+		"user",
+		"delete",
+	)?;
+	let response: ReauthResponse = bincode::deserialize(&bincode::serialize(&response)?)?;
+	assert_eq!(response.challenge().action(), "delete");
+	let (_, finalization, _) = client.finish(response)?;
+	let proof = server.finish(&server_config, finalization)?;
+
+	let proof: ReauthProof = bincode::deserialize(&bincode::serialize(&proof)?)?;
+	assert_eq!(proof.subject(), "user");
+	assert_eq!(proof.action(), "delete");
+	proof.verify(&server_config, "user", "delete", MAX_AGE)?;
+	assert_eq!(
+		proof.verify(&server_config, "user", "email", MAX_AGE),
+		Err(Error::ReauthProof)
+	);
+	assert_eq!(
+		proof.verify(&server_config, "other", "delete", MAX_AGE),
+		Err(Error::ReauthProof)
+	);
+	assert_eq!(
+		proof.verify(&ServerConfig::default(), "user", "delete", MAX_AGE),
+		Err(Error::ReauthProof)
+	);
+
+	// expiry
+	let proof = ReauthProof::new(
+		&server_config,
+		String::from("user"),
+		String::from("delete"),
+		ledger::now() - 120,
+	);
+	assert_eq!(
+		proof.verify(&server_config, "user", "delete", MAX_AGE),
+		Err(Error::ReauthExpired)
+	);
+
+	// tampered action
+	let (client, request) =
+		ClientReauth::reauthenticate(client_config, Some(client_file), PASSWORD)?;
+	let (_, mut response) =
+		ServerReauth::reauthenticate(&server_config, Some(server_file), request, "user", "delete")?;
+	response.challenge.action = String::from("email");
+	assert_eq!(client.finish(response), Err(Error::Credentials));
+
+	Ok(())
+}
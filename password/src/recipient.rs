@@ -0,0 +1,176 @@
+#![allow(clippy::module_name_repetitions)]
+
+//! Public-key encryption to a recipient, e.g. a trustee or an administrator.
+
+use std::fmt::Debug;
+
+use curve25519_dalek::{
+	constants::RISTRETTO_BASEPOINT_POINT,
+	ristretto::{CompressedRistretto, RistrettoPoint},
+	scalar::Scalar,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+	crypto::{self, NONCE_SIZE},
+	Error, Result,
+};
+
+/// Secret key of a recipient, used to decrypt data encrypted to the
+/// corresponding [`RecipientPublicKey`].
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct RecipientSecretKey([u8; 32]);
+
+impl RecipientSecretKey {
+	/// Generates a new random [`RecipientSecretKey`].
+	#[must_use]
+	pub fn generate() -> Self {
+		let bytes = Zeroizing::new(crypto::random::<64>());
+		Self(Scalar::from_bytes_mod_order_wide(&bytes).to_bytes())
+	}
+
+	/// Returns the [`RecipientPublicKey`] corresponding to this
+	/// [`RecipientSecretKey`].
+	#[must_use]
+	pub fn public_key(&self) -> RecipientPublicKey {
+		RecipientPublicKey(
+			(RISTRETTO_BASEPOINT_POINT * self.scalar())
+				.compress()
+				.to_bytes(),
+		)
+	}
+
+	/// Returns the [`Scalar`] of this key.
+	fn scalar(&self) -> Scalar {
+		Scalar::from_bytes_mod_order(self.0)
+	}
+
+	/// Decrypts a [`Sealed`] encrypted to the corresponding
+	/// [`RecipientPublicKey`] with the same `info`.
+	pub(crate) fn decrypt(&self, info: &[u8], sealed: &Sealed) -> Result<Zeroizing<Vec<u8>>> {
+		let ephemeral = CompressedRistretto(sealed.ephemeral)
+			.decompress()
+			.ok_or(Error::Decryption)?;
+		let key = derive_key(
+			&(ephemeral * self.scalar()),
+			&sealed.ephemeral,
+			&self.public_key(),
+			info,
+		);
+
+		crypto::open(&key, &sealed.nonce, info, &sealed.ciphertext).ok_or(Error::Decryption)
+	}
+}
+
+crypto::impl_secret!(RecipientSecretKey);
+
+/// Public key of a recipient, data encrypted to it can only be decrypted with
+/// the corresponding [`RecipientSecretKey`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct RecipientPublicKey([u8; 32]);
+
+impl RecipientPublicKey {
+	/// Create a [`RecipientPublicKey`] from bytes.
+	///
+	/// # Errors
+	/// [`Error::RecipientPublicKey`] if `bytes` isn't a valid public key.
+	pub fn from_bytes(bytes: [u8; 32]) -> Result<Self> {
+		let public_key = Self(bytes);
+		public_key.point()?;
+
+		Ok(public_key)
+	}
+
+	/// Returns the bytes of this key.
+	#[must_use]
+	pub const fn as_bytes(&self) -> &[u8; 32] {
+		&self.0
+	}
+
+	/// Returns the [`RistrettoPoint`] of this key.
+	fn point(self) -> Result<RistrettoPoint> {
+		CompressedRistretto(self.0)
+			.decompress()
+			.ok_or(Error::RecipientPublicKey)
+	}
+
+	/// Encrypts `plaintext` to this [`RecipientPublicKey`]. The same `info`
+	/// has to be used to decrypt it again.
+	pub(crate) fn encrypt(self, info: &[u8], plaintext: &[u8]) -> Result<Sealed> {
+		let secret = RecipientSecretKey::generate();
+		let ephemeral = secret.public_key();
+		let key = derive_key(
+			&(self.point()? * secret.scalar()),
+			&ephemeral.0,
+			&self,
+			info,
+		);
+		let (nonce, ciphertext) = crypto::seal(&key, info, plaintext);
+
+		Ok(Sealed {
+			ephemeral: ephemeral.0,
+			nonce,
+			ciphertext,
+		})
+	}
+}
+
+/// Data encrypted to a [`RecipientPublicKey`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub(crate) struct Sealed {
+	/// Ephemeral public key.
+	ephemeral: [u8; 32],
+	/// Nonce used to encrypt.
+	nonce: [u8; NONCE_SIZE],
+	/// Encrypted data.
+	ciphertext: Vec<u8>,
+}
+
+/// Derives the encryption key from a Diffie-Hellman result, binding it to both
+/// public keys.
+fn derive_key(
+	shared: &RistrettoPoint,
+	ephemeral: &[u8; 32],
+	recipient: &RecipientPublicKey,
+	info: &[u8],
+) -> Zeroizing<[u8; 32]> {
+	let shared = Zeroizing::new(shared.compress().to_bytes());
+	crypto::derive_key(
+		shared.as_ref(),
+		&[&ephemeral[..], recipient.as_bytes()].concat(),
+		info,
+	)
+}
+
+#[test]
+fn recipient() -> anyhow::Result<()> {
+	let secret_key = RecipientSecretKey::generate();
+	let public_key = secret_key.public_key();
+	assert_eq!(
+		RecipientPublicKey::from_bytes(*public_key.as_bytes())?,
+		public_key
+	);
+
+	let sealed = public_key.encrypt(b"info", b"plaintext")?;
+	assert_eq!(
+		secret_key.decrypt(b"info", &sealed)?.as_slice(),
+		b"plaintext"
+	);
+	assert_eq!(
+		secret_key.decrypt(b"other", &sealed),
+		Err(Error::Decryption)
+	);
+	assert_eq!(
+		RecipientSecretKey::generate().decrypt(b"info", &sealed),
+		Err(Error::Decryption)
+	);
+
+	assert_eq!(
+		RecipientPublicKey::from_bytes([0xff; 32]),
+		Err(Error::RecipientPublicKey)
+	);
+
+	Ok(())
+}
@@ -0,0 +1,281 @@
+#![allow(clippy::module_name_repetitions)]
+
+//! Recovery codes, an alternative credential to the users password.
+
+use std::{
+	fmt::{self, Debug, Display, Formatter},
+	str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::{
+	crypto, Error, LoginFinalization, LoginRequest, LoginResponse, Result, ServerConfig,
+	ServerFile, ServerLogin, SessionKey,
+};
+
+/// Crockford's Base32 alphabet.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+/// Number of random bytes in a [`RecoveryCode`].
+const BYTES: usize = 20;
+/// Number of characters in a [`RecoveryCode`].
+const LENGTH: usize = BYTES * 8 / 5;
+/// Number of characters per group when displaying a [`RecoveryCode`].
+const GROUP: usize = 4;
+
+/// High-entropy code that can be registered and used like a password, see
+/// [`ServerRecovery`].
+///
+/// Recovery codes are 160-bit random values encoded in Crockford's Base32 and
+/// displayed in groups of four, e.g. `7D3K-9W2M-...`. Parsing is
+/// case-insensitive, ignores separators and accepts commonly confused
+/// characters.
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+	/// Generates a new random [`RecoveryCode`].
+	#[must_use]
+	pub fn generate() -> Self {
+		let bytes = crypto::random::<BYTES>();
+		let mut code = String::with_capacity(LENGTH);
+
+		for chunk in bytes.chunks(5) {
+			let mut bits = 0_u64;
+
+			for byte in chunk {
+				bits = (bits << 8) | u64::from(*byte);
+			}
+
+			for index in (0..8).rev() {
+				#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+				let symbol = ((bits >> (index * 5)) & 0x1f) as usize;
+				code.push(char::from(ALPHABET[symbol]));
+			}
+		}
+
+		Self(code)
+	}
+}
+
+crypto::impl_secret!(RecoveryCode, |this, other| this
+	.0
+	.as_bytes()
+	.ct_eq(other.0.as_bytes()));
+
+impl AsRef<[u8]> for RecoveryCode {
+	fn as_ref(&self) -> &[u8] {
+		self.0.as_bytes()
+	}
+}
+
+impl Display for RecoveryCode {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		for (index, group) in self.0.as_bytes().chunks(GROUP).enumerate() {
+			if index != 0 {
+				f.write_str("-")?;
+			}
+
+			f.write_str(std::str::from_utf8(group).map_err(|_| fmt::Error)?)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl FromStr for RecoveryCode {
+	type Err = Error;
+
+	fn from_str(code: &str) -> Result<Self> {
+		let mut canonical = String::with_capacity(LENGTH);
+
+		for character in code.chars() {
+			let character = match character.to_ascii_uppercase() {
+				'-' | ' ' => continue,
+				'O' => '0',
+				'I' | 'L' => '1',
+				character
+					if ALPHABET
+						.iter()
+						.any(|symbol| char::from(*symbol) == character) =>
+					character,
+				_ => return Err(Error::RecoveryCode),
+			};
+
+			canonical.push(character);
+		}
+
+		if canonical.len() == LENGTH {
+			Ok(Self(canonical))
+		} else {
+			Err(Error::RecoveryCode)
+		}
+	}
+}
+
+/// [`ServerFile`] of a registered [`RecoveryCode`]. Keeps track if it was
+/// already used, see [`ServerRecovery`].
+#[must_use = "Without this the client can't recover"]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct RecoveryFile {
+	/// [`ServerFile`] of the [`RecoveryCode`].
+	file: ServerFile,
+	/// If this [`RecoveryFile`] was already used.
+	used: bool,
+}
+
+impl RecoveryFile {
+	/// Create a new unused [`RecoveryFile`] from a [`ServerFile`] registered
+	/// with a [recovery configuration](ServerConfig::to_recovery).
+	pub const fn new(file: ServerFile) -> Self {
+		Self { file, used: false }
+	}
+
+	/// Returns the [`ServerFile`] of this [`RecoveryFile`].
+	pub const fn file(&self) -> &ServerFile {
+		&self.file
+	}
+
+	/// Returns `true` if this [`RecoveryFile`] was already used to login.
+	#[must_use]
+	pub const fn is_used(&self) -> bool {
+		self.used
+	}
+}
+
+/// Login with a [`RecoveryCode`]. Each [`RecoveryFile`] can only be used once.
+///
+/// Recovery codes are registered like passwords, with
+/// [`ClientRegistration`](crate::ClientRegistration) and
+/// [`ServerRegistration`](crate::ServerRegistration), but using
+/// [`ClientConfig::to_recovery()`](crate::ClientConfig::to_recovery) and
+/// [`ServerConfig::to_recovery()`]. The resulting
+/// [`ExportKey`](crate::ExportKey) can be used to add
+/// [`KeySlot::Recovery`](crate::KeySlot::Recovery) to a
+/// [`WrappedKey`](crate::WrappedKey).
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[must_use = "Does nothing if not `finish`ed"]
+pub struct ServerRecovery {
+	/// Login process state.
+	login: ServerLogin,
+	/// [`ServerFile`] of the [`RecoveryFile`] used to login.
+	file: Option<ServerFile>,
+}
+
+impl ServerRecovery {
+	/// Starts the login process with a [`RecoveryCode`]. The client has to use
+	/// [`ClientLogin`](crate::ClientLogin) with a [recovery
+	/// configuration](crate::ClientConfig::to_recovery).
+	///
+	/// An already used [`RecoveryFile`] is treated like an unregistered client,
+	/// see [`ServerLogin::login()`].
+	///
+	/// # Errors
+	/// See [`ServerLogin::login()`].
+	pub fn login(
+		config: &ServerConfig,
+		file: Option<RecoveryFile>,
+		request: LoginRequest,
+	) -> Result<(Self, LoginResponse)> {
+		let file = file.filter(|file| !file.used).map(|file| file.file);
+		let (login, response) = ServerLogin::login(config, file.clone(), request)?;
+
+		Ok((Self { login, file }, response))
+	}
+
+	/// Finishes the login process. The returned [`RecoveryFile`] is marked as
+	/// used and has to replace the stored one. See [`ServerLogin::finish()`]
+	/// for the [`SessionKey`].
+	///
+	/// # Errors
+	/// - [`Error::Credentials`] if no unused [`RecoveryFile`] was passed
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn finish(self, finalization: LoginFinalization) -> Result<(RecoveryFile, SessionKey)> {
+		let (session_key, _) = self.login.finish(finalization)?;
+
+		Ok((
+			RecoveryFile {
+				file: self.file.ok_or(Error::Credentials)?,
+				used: true,
+			},
+			session_key,
+		))
+	}
+}
+
+#[test]
+fn recovery_code() -> anyhow::Result<()> {
+	let code = RecoveryCode::generate();
+	assert_eq!(code.as_ref().len(), LENGTH);
+
+	let display = code.to_string();
+	assert_eq!(display.len(), LENGTH + LENGTH / GROUP - 1);
+	assert_eq!(display.parse::<RecoveryCode>()?, code);
+	assert_eq!(
+		display
+			.to_ascii_lowercase()
+			.replace('-', " ")
+			.parse::<RecoveryCode>()?,
+		code
+	);
+
+	let code: RecoveryCode = "oIl0-0000-0000-0000-0000-0000-0000-0000".parse()?;
+	assert_eq!(code.as_ref(), b"01100000000000000000000000000000");
+
+	assert_eq!("0000".parse::<RecoveryCode>(), Err(Error::RecoveryCode));
+	assert_eq!(
+		"U000-0000-0000-0000-0000-0000-0000-0000".parse::<RecoveryCode>(),
+		Err(Error::RecoveryCode)
+	);
+
+	Ok(())
+}
+
+#[test]
+fn recovery() -> anyhow::Result<()> {
+	use crate::{
+		ClientConfig, ClientLogin, ClientRegistration, KeySlot, ServerRegistration, WrappedKey,
+	};
+
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config =
+		ClientConfig::new(server_config.config(), Some(server_config.public_key()))?;
+	let recovery_server_config = server_config.to_recovery();
+	let recovery_client_config = client_config.to_recovery();
+
+	// password registration
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, export_key) = client.finish(response)?;
+	let _file = server.finish(finalization)?;
+	let (mut wrapped_key, data_key) = WrappedKey::new(&export_key);
+
+	// recovery code registration
+	let code = RecoveryCode::generate();
+	let (client, request) = ClientRegistration::register(recovery_client_config, &code)?;
+	let (server, response) = ServerRegistration::register(&recovery_server_config, request)?;
+	let (_, finalization, recovery_key) = client.finish(response)?;
+	let file = RecoveryFile::new(server.finish(finalization)?);
+	wrapped_key.add_slot(KeySlot::Recovery, &data_key, &recovery_key);
+
+	// recovery
+	let (client, request) = ClientLogin::login(recovery_client_config, None, &code)?;
+	let (server, response) = ServerRecovery::login(&recovery_server_config, Some(file), request)?;
+	let (_, finalization, recovery_key, _) = client.finish(response)?;
+	let (file, _) = server.finish(finalization)?;
+	assert!(file.is_used());
+	assert_eq!(
+		wrapped_key.unlock(KeySlot::Recovery, &recovery_key)?,
+		data_key
+	);
+
+	// second recovery
+	let (client, request) = ClientLogin::login(recovery_client_config, None, &code)?;
+	let (_, response) = ServerRecovery::login(&recovery_server_config, Some(file), request)?;
+	assert_eq!(client.finish(response), Err(Error::Credentials));
+
+	Ok(())
+}
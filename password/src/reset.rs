@@ -0,0 +1,248 @@
+//! Administrator-initiated password reset, see [`ServerReset`].
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	ledger::{IdentifierToken, Rejection, TokenId},
+	Config, Error, KeySlot, RegistrationFinalization, RegistrationRequest, RegistrationResponse,
+	Result, ServerConfig, ServerFile, ServerRegistration, TokenLedger, WrappedKey,
+};
+
+/// HKDF info used to derive the key authenticating [`ResetToken`]s.
+const INFO: &[u8] = b"custodian-password reset";
+
+/// Single-use token issued by an administrator, authorizes setting a new
+/// password for a credential identifier. See [`ServerReset::reset()`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct ResetToken(IdentifierToken);
+
+impl ResetToken {
+	/// Issues a new [`ResetToken`] for the credential `identifier`, valid for
+	/// `validity`.
+	#[must_use]
+	pub fn issue(config: &ServerConfig, identifier: &str, validity: Duration) -> Self {
+		Self(IdentifierToken::issue(config, INFO, identifier, validity))
+	}
+
+	/// Returns the [`TokenId`] of this [`ResetToken`], use it to
+	/// [revoke](TokenLedger::revoke) it.
+	#[must_use]
+	pub const fn id(&self) -> TokenId {
+		self.0.id()
+	}
+
+	/// Returns the credential identifier this [`ResetToken`] was issued for.
+	#[must_use]
+	pub fn identifier(&self) -> &str {
+		self.0.identifier()
+	}
+
+	/// Returns the expiry of this [`ResetToken`].
+	#[must_use]
+	pub fn expires(&self) -> SystemTime {
+		self.0.expires()
+	}
+}
+
+/// Access to data protected by the old [`ExportKey`](crate::ExportKey) after
+/// a reset. The new [`ExportKey`](crate::ExportKey) can't decrypt it.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum DataAccess {
+	/// No [`WrappedKey`] slot other than [`KeySlot::Password`] exists, data
+	/// protected by the old [`ExportKey`](crate::ExportKey) is lost.
+	Lost,
+	/// The [`DataKey`](crate::DataKey) can still be unlocked through these
+	/// slots, afterwards [`KeySlot::Password`] should be replaced with the new
+	/// [`ExportKey`](crate::ExportKey).
+	Recoverable(Vec<KeySlot>),
+}
+
+impl DataAccess {
+	/// Determines the [`DataAccess`] of a [`WrappedKey`].
+	fn new(wrapped_key: Option<&WrappedKey>) -> Self {
+		let slots: Vec<_> = wrapped_key
+			.into_iter()
+			.flat_map(WrappedKey::slots)
+			.filter(|slot| *slot != KeySlot::Password)
+			.collect();
+
+		if slots.is_empty() {
+			Self::Lost
+		} else {
+			Self::Recoverable(slots)
+		}
+	}
+}
+
+/// Holds the state of a password reset on the server. See
+/// [`reset()`](Self::reset).
+#[must_use = "Does nothing if not `finish`ed"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ServerReset {
+	/// Underlying registration process.
+	registration: ServerRegistration,
+}
+
+impl ServerReset {
+	/// Returns the [`Config`] associated with this [`ServerReset`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.registration.config()
+	}
+
+	/// Starts a password reset for the credential `identifier` with a
+	/// [`RegistrationRequest`] of a fresh
+	/// [`ClientRegistration`](crate::ClientRegistration). The [`ResetToken`] is
+	/// marked as used in `ledger`, even if the reset isn't finished.
+	///
+	/// # Errors
+	/// - [`Error::ResetToken`] if the [`ResetToken`] wasn't issued by this
+	///   [`ServerConfig`] or for a different `identifier`
+	/// - [`Error::ResetTokenExpired`] if the [`ResetToken`] expired
+	/// - [`Error::ResetTokenUsed`] if the [`ResetToken`] was already used or
+	///   revoked
+	/// - [`Error::Config`] if [`ServerConfig`] and [`RegistrationRequest`] were
+	///   not created with the same [`Config`]
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	pub fn reset(
+		config: &ServerConfig,
+		request: RegistrationRequest,
+		token: &ResetToken,
+		identifier: &str,
+		ledger: &mut TokenLedger,
+	) -> Result<(Self, RegistrationResponse)> {
+		token
+			.0
+			.check(config, INFO, ledger)
+			.map_err(|rejection| match rejection {
+				Rejection::Invalid => Error::ResetToken,
+				Rejection::Expired => Error::ResetTokenExpired,
+				Rejection::Used => Error::ResetTokenUsed,
+			})?;
+
+		if token.identifier() != identifier {
+			return Err(Error::ResetToken);
+		}
+
+		let (registration, response) = ServerRegistration::register_unchecked(config, request)?;
+		token.0.spend(ledger);
+
+		Ok((Self { registration }, response))
+	}
+
+	/// Finishes the password reset. The returned [`ServerFile`] replaces the
+	/// old one.
+	///
+	/// Data protected by the old [`ExportKey`](crate::ExportKey) can only be
+	/// recovered through other slots of the users [`WrappedKey`], see
+	/// [`DataAccess`].
+	///
+	/// # Errors
+	/// [`Error::Config`] if [`ServerReset`] and [`RegistrationFinalization`]
+	/// were not created with the same [`Config`].
+	pub fn finish(
+		self,
+		finalization: RegistrationFinalization,
+		wrapped_key: Option<&WrappedKey>,
+	) -> Result<(ServerFile, DataAccess)> {
+		let file = self.registration.finish(finalization)?;
+
+		Ok((file, DataAccess::new(wrapped_key)))
+	}
+}
+
+#[test]
+fn reset() -> anyhow::Result<()> {
+	use crate::{ClientConfig, ClientLogin, ClientRegistration, ServerLogin};
+
+	const PASSWORD: &[u8] = b"password";
+	const NEW_PASSWORD: &[u8] = b"new password";
+	const VALIDITY: Duration = Duration::from_secs(60);
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+	let mut ledger = TokenLedger::new();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, export_key) = client.finish(response)?;
+	let _file = server.finish(finalization)?;
+	let (mut wrapped_key, data_key) = WrappedKey::new(&export_key);
+
+	// reset without recovery slot
+	let token = ResetToken::issue(&server_config, "user", VALIDITY);
+	let token: ResetToken = bincode::deserialize(&bincode::serialize(&token)?)?;
+	let (client, request) = ClientRegistration::register(client_config, NEW_PASSWORD)?;
+	let (server, response) =
+		ServerReset::reset(&server_config, request.clone(), &token, "user", &mut ledger)?;
+	let (client_file, finalization, new_export_key) = client.finish(response)?;
+	let (server_file, access) = server.finish(finalization, Some(&wrapped_key))?;
+	assert_eq!(access, DataAccess::Lost);
+	assert_ne!(export_key, new_export_key);
+
+	let (client, login_request) =
+		ClientLogin::login(client_config, Some(client_file), NEW_PASSWORD)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), login_request)?;
+	let (_, finalization, ..) = client.finish(response)?;
+	server.finish(finalization)?;
+
+	// reuse
+	assert_eq!(
+		ServerReset::reset(&server_config, request, &token, "user", &mut ledger),
+		Err(Error::ResetTokenUsed)
+	);
+
+	// reset with recovery slot
+	wrapped_key.add_slot(KeySlot::Recovery, &data_key, &export_key);
+	let token = ResetToken::issue(&server_config, "user", VALIDITY);
+	let (client, request) = ClientRegistration::register(client_config, NEW_PASSWORD)?;
+	let (server, response) =
+		ServerReset::reset(&server_config, request.clone(), &token, "user", &mut ledger)?;
+	let (_, finalization, _) = client.finish(response)?;
+	let (_, access) = server.finish(finalization, Some(&wrapped_key))?;
+	assert_eq!(access, DataAccess::Recoverable(vec![KeySlot::Recovery]));
+	assert_eq!(DataAccess::new(None), DataAccess::Lost);
+
+	// different identifier
+	let token = ResetToken::issue(&server_config, "user", VALIDITY);
+	assert_eq!(
+		ServerReset::reset(
+			&server_config,
+			request.clone(),
+			&token,
+			"admin",
+			&mut ledger
+		),
+		Err(Error::ResetToken)
+	);
+
+	// different server
+	assert_eq!(
+		ServerReset::reset(
+			&ServerConfig::default(),
+			request.clone(),
+			&token,
+			"user",
+			&mut ledger
+		),
+		Err(Error::ResetToken)
+	);
+
+	// revoked
+	ledger.revoke(token.id(), Some(token.expires()));
+	assert_eq!(
+		ServerReset::reset(&server_config, request.clone(), &token, "user", &mut ledger),
+		Err(Error::ResetTokenUsed)
+	);
+
+	// expired
+	let token = ResetToken::issue(&server_config, "user", Duration::ZERO);
+	assert_eq!(
+		ServerReset::reset(&server_config, request, &token, "user", &mut ledger),
+		Err(Error::ResetTokenExpired)
+	);
+
+	Ok(())
+}
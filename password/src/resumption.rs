@@ -0,0 +1,397 @@
+//! Session resumption, skipping the [`Mhf`](crate::Mhf) on reconnect. See
+//! [`ResumptionTicket`].
+
+use std::{
+	convert::TryInto,
+	fmt::Debug,
+	time::{Duration, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use zeroize::Zeroize;
+
+use crate::{
+	crypto::{self, NONCE_SIZE, TAG_SIZE},
+	ledger::{self, TokenId},
+	ClientLogin, Config, Error, Result, ServerConfig, ServerLogin, SessionKey, TokenLedger,
+};
+
+/// HKDF info used to derive the key encrypting [`ResumptionTicket`]s.
+const TICKET_INFO: &[u8] = b"custodian-password ticket";
+/// HKDF info used to derive the ticket secret from a [`SessionKey`].
+const SECRET_INFO: &[u8] = b"custodian-password resumption secret";
+/// Domain separation for the proof of possession of the ticket secret.
+const PROOF_INFO: &[u8] = b"custodian-password resumption proof";
+/// HKDF info used to derive the resumed [`SessionKey`].
+const SESSION_INFO: &[u8] = b"custodian-password resumption session";
+
+/// Ticket issued by the server after a successful login, allows the client to
+/// login again without running the [`Mhf`](crate::Mhf). See
+/// [`ClientLogin::resume()`] and [`ServerLogin::resume()`].
+///
+/// Tickets are bound to a subject chosen by the server, e.g. the username,
+/// which is returned again on resumption. They expire, can only be used once
+/// and can be revoked with a [`TokenLedger`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ResumptionTicket {
+	/// [`Config`] of the corresponding [`ServerConfig`].
+	config: Config,
+	/// Identifies this ticket in a [`TokenLedger`].
+	id: TokenId,
+	/// Subject the ticket was issued to.
+	subject: String,
+	/// Expiry in seconds since the Unix epoch.
+	expires: u64,
+	/// Nonce used to encrypt the ticket secret.
+	nonce: [u8; NONCE_SIZE],
+	/// Ticket secret encrypted with the ticket key of the [`ServerConfig`].
+	#[serde(with = "BigArray")]
+	secret: [u8; 32 + TAG_SIZE],
+}
+
+impl ResumptionTicket {
+	/// Issues a new [`ResumptionTicket`] for the [`SessionKey`] returned by
+	/// [`ServerLogin::finish()`] or [`ServerLogin::resume()`], valid for
+	/// `validity`. `subject` identifies who logged in and is returned by
+	/// [`ServerLogin::resume()`]. Has to be sent to the client.
+	#[must_use]
+	pub fn issue(
+		config: &ServerConfig,
+		subject: &str,
+		session_key: &SessionKey,
+		validity: Duration,
+	) -> Self {
+		let id = TokenId::new();
+		let expires = ledger::expiry(validity);
+		let (nonce, secret) =
+			Secret::new(session_key, id).seal(config, &Self::aad(id, subject, expires));
+
+		Self {
+			config: config.config(),
+			id,
+			subject: subject.to_owned(),
+			expires,
+			nonce,
+			secret,
+		}
+	}
+
+	/// Returns the [`Config`] associated with this [`ResumptionTicket`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.config
+	}
+
+	/// Returns the [`TokenId`] of this [`ResumptionTicket`], use it to
+	/// [revoke](TokenLedger::revoke) it.
+	#[must_use]
+	pub const fn id(&self) -> TokenId {
+		self.id
+	}
+
+	/// Returns the subject this [`ResumptionTicket`] was issued to.
+	#[must_use]
+	pub fn subject(&self) -> &str {
+		&self.subject
+	}
+
+	/// Returns the expiry of this [`ResumptionTicket`].
+	#[must_use]
+	pub fn expires(&self) -> SystemTime {
+		ledger::from_timestamp(self.expires)
+	}
+
+	/// Data authenticated with the ticket secret.
+	fn aad(id: TokenId, subject: &str, expires: u64) -> Vec<u8> {
+		[
+			&id.as_bytes()[..],
+			&expires.to_be_bytes(),
+			subject.as_bytes(),
+		]
+		.concat()
+	}
+}
+
+/// Secret shared between client and server through a [`ResumptionTicket`].
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+struct Secret([u8; 32]);
+
+impl Secret {
+	/// Derives the ticket secret from a [`SessionKey`].
+	fn new(session_key: &SessionKey, id: TokenId) -> Self {
+		Self(*crypto::derive_key(
+			session_key.as_slice(),
+			id.as_bytes(),
+			SECRET_INFO,
+		))
+	}
+
+	/// Encrypts the ticket secret with the ticket key of the [`ServerConfig`].
+	fn seal(&self, config: &ServerConfig, aad: &[u8]) -> ([u8; NONCE_SIZE], [u8; 32 + TAG_SIZE]) {
+		let (nonce, secret) = crypto::seal(&config.derive_key(TICKET_INFO), aad, &self.0);
+
+		(
+			nonce,
+			secret.as_slice().try_into().expect("unexpected size"),
+		)
+	}
+
+	/// Derives the resumed [`SessionKey`].
+	fn session_key(&self, nonce: &[u8; 32]) -> SessionKey {
+		SessionKey::new(
+			crypto::derive_key(&self.0, nonce, SESSION_INFO)
+				.as_slice()
+				.try_into()
+				.expect("unexpected size"),
+		)
+	}
+}
+
+crypto::impl_secret!(Secret);
+
+/// [`ResumptionTicket`] stored by the client together with the ticket secret.
+/// See [`ClientLogin::resume()`].
+#[must_use = "Without this the client can't resume"]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ClientTicket {
+	/// [`ResumptionTicket`] issued by the server.
+	ticket: ResumptionTicket,
+	/// Ticket secret.
+	secret: Secret,
+}
+
+impl ClientTicket {
+	/// Stores a [`ResumptionTicket`] received from the server, the
+	/// [`SessionKey`] has to be the one returned by
+	/// [`ClientLogin::finish()`] or [`ClientLogin::resume()`].
+	pub fn new(ticket: ResumptionTicket, session_key: &SessionKey) -> Self {
+		let secret = Secret::new(session_key, ticket.id);
+
+		Self { ticket, secret }
+	}
+
+	/// Returns the [`ResumptionTicket`] of this [`ClientTicket`].
+	#[must_use]
+	pub const fn ticket(&self) -> &ResumptionTicket {
+		&self.ticket
+	}
+}
+
+/// Send this to the server to resume a session. See
+/// [`ServerLogin::resume()`].
+#[must_use = "Does nothing if not sent to the server"]
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ResumptionRequest {
+	/// [`ResumptionTicket`] issued by the server.
+	ticket: ResumptionTicket,
+	/// Random nonce of the client.
+	nonce: [u8; 32],
+	/// Proof of possession of the ticket secret.
+	proof: [u8; 32],
+}
+
+impl ResumptionRequest {
+	/// Returns [`Config`] used to create this [`ResumptionRequest`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.ticket.config
+	}
+}
+
+impl ClientLogin {
+	/// Resumes a session with a [`ClientTicket`], without running the
+	/// [`Mhf`](crate::Mhf). The returned [`ResumptionRequest`] has to be sent
+	/// to the server, see [`ServerLogin::resume()`].
+	///
+	/// Every [`ClientTicket`] can only be used once, the server can issue a
+	/// new one with the returned [`SessionKey`].
+	pub fn resume(ticket: ClientTicket) -> (ResumptionRequest, SessionKey) {
+		let nonce = crypto::random();
+		let proof = crypto::mac(&ticket.secret.0, &[
+			PROOF_INFO,
+			ticket.ticket.id.as_bytes(),
+			&nonce,
+		]);
+		let session_key = ticket.secret.session_key(&nonce);
+
+		(
+			ResumptionRequest {
+				ticket: ticket.ticket,
+				nonce,
+				proof,
+			},
+			session_key,
+		)
+	}
+}
+
+impl ServerLogin {
+	/// Resumes a session with a [`ResumptionRequest`]. The [`ResumptionTicket`]
+	/// is marked as used in `ledger`. Returns the [`SessionKey`] shared with
+	/// the client and the subject the [`ResumptionTicket`] was issued to.
+	///
+	/// # Errors
+	/// - [`Error::Config`] if [`ServerConfig`] and [`ResumptionRequest`] were
+	///   not created with the same [`Config`]
+	/// - [`Error::Ticket`] if the [`ResumptionTicket`] wasn't issued by this
+	///   [`ServerConfig`] or the client couldn't prove possession
+	/// - [`Error::TicketExpired`] if the [`ResumptionTicket`] expired
+	/// - [`Error::TicketUsed`] if the [`ResumptionTicket`] was already used or
+	///   revoked
+	pub fn resume(
+		config: &ServerConfig,
+		request: ResumptionRequest,
+		ledger: &mut TokenLedger,
+	) -> Result<(SessionKey, String)> {
+		let ResumptionRequest {
+			ticket,
+			nonce,
+			proof,
+		} = request;
+
+		if config.config() != ticket.config {
+			return Err(Error::Config);
+		}
+
+		let secret = crypto::open(
+			&config.derive_key(TICKET_INFO),
+			&ticket.nonce,
+			&ResumptionTicket::aad(ticket.id, &ticket.subject, ticket.expires),
+			&ticket.secret,
+		)
+		.ok_or(Error::Ticket)?;
+		let secret = Secret(secret.as_slice().try_into().map_err(|_| Error::Ticket)?);
+
+		if ledger::now() >= ticket.expires {
+			return Err(Error::TicketExpired);
+		}
+
+		if !crypto::verify(
+			&secret.0,
+			&[PROOF_INFO, ticket.id.as_bytes(), &nonce],
+			&proof,
+		) {
+			return Err(Error::Ticket);
+		}
+
+		if !ledger.spend(ticket.id, ticket.expires) {
+			return Err(Error::TicketUsed);
+		}
+
+		Ok((secret.session_key(&nonce), ticket.subject))
+	}
+}
+
+#[test]
+fn resumption() -> anyhow::Result<()> {
+	use crate::{ClientConfig, ClientRegistration, ServerRegistration};
+
+	const PASSWORD: &[u8] = b"password";
+	const SUBJECT: &str = "user";
+	const VALIDITY: Duration = Duration::from_secs(60);
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+	let mut ledger = TokenLedger::new();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, _, client_session_key) = client.finish(response)?;
+	let (server_session_key, _) = server.finish(finalization)?;
+
+	// issue ticket
+	let ticket = ResumptionTicket::issue(&server_config, SUBJECT, &server_session_key, VALIDITY);
+	let ticket: ResumptionTicket = bincode::deserialize(&bincode::serialize(&ticket)?)?;
+	let client_ticket = ClientTicket::new(ticket, &client_session_key);
+
+	// resume
+	let (request, client_session_key) = ClientLogin::resume(client_ticket.clone());
+	let (server_session_key, subject) =
+		ServerLogin::resume(&server_config, request.clone(), &mut ledger)?;
+	assert_eq!(client_session_key, server_session_key);
+	assert_eq!(subject, SUBJECT);
+	assert_ne!(client_session_key, server_session_key_of(&server_config));
+
+	// single-use
+	assert_eq!(
+		ServerLogin::resume(&server_config, request, &mut ledger),
+		Err(Error::TicketUsed)
+	);
+	let (request, _) = ClientLogin::resume(client_ticket);
+	assert_eq!(
+		ServerLogin::resume(&server_config, request, &mut ledger),
+		Err(Error::TicketUsed)
+	);
+
+	// rotate ticket
+	let ticket = ResumptionTicket::issue(&server_config, SUBJECT, &server_session_key, VALIDITY);
+	let client_ticket = ClientTicket::new(ticket, &client_session_key);
+
+	// revocation
+	ledger.revoke(
+		client_ticket.ticket().id(),
+		Some(client_ticket.ticket().expires()),
+	);
+	let (request, _) = ClientLogin::resume(client_ticket);
+	assert_eq!(
+		ServerLogin::resume(&server_config, request, &mut ledger),
+		Err(Error::TicketUsed)
+	);
+
+	// expiry
+	let ticket =
+		ResumptionTicket::issue(&server_config, SUBJECT, &server_session_key, Duration::ZERO);
+	let (request, _) = ClientLogin::resume(ClientTicket::new(ticket, &client_session_key));
+	assert_eq!(
+		ServerLogin::resume(&server_config, request, &mut ledger),
+		Err(Error::TicketExpired)
+	);
+
+	// changed subject
+	let mut ticket =
+		ResumptionTicket::issue(&server_config, SUBJECT, &server_session_key, VALIDITY);
+	ticket.subject = String::from("other");
+	let (request, _) = ClientLogin::resume(ClientTicket::new(ticket, &client_session_key));
+	assert_eq!(
+		ServerLogin::resume(&server_config, request, &mut ledger),
+		Err(Error::Ticket)
+	);
+
+	// wrong secret
+	let ticket = ResumptionTicket::issue(&server_config, SUBJECT, &server_session_key, VALIDITY);
+	let (request, _) = ClientLogin::resume(ClientTicket::new(
+		ticket.clone(),
+		&server_session_key_of(&server_config),
+	));
+	assert_eq!(
+		ServerLogin::resume(&server_config, request, &mut ledger),
+		Err(Error::Ticket)
+	);
+
+	// wrong server
+	let (request, _) = ClientLogin::resume(ClientTicket::new(ticket, &client_session_key));
+	assert_eq!(
+		ServerLogin::resume(&ServerConfig::default(), request, &mut ledger),
+		Err(Error::Ticket)
+	);
+
+	Ok(())
+}
+
+/// Returns an unrelated [`SessionKey`] for testing.
+#[cfg(test)]
+fn server_session_key_of(config: &ServerConfig) -> SessionKey {
+	SessionKey::new(
+		config
+			.derive_key(b"test")
+			.as_slice()
+			.try_into()
+			.expect("unexpected size"),
+	)
+}
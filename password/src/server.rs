@@ -2,48 +2,128 @@
 
 //! OPAQUE server side handling.
 
+use std::fmt::{self, Debug, Formatter};
+
 use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+use zeroize::Zeroizing;
 
 use crate::{
 	cipher_suite::{self, ServerSetup},
-	Config, Error, LoginFinalization, LoginRequest, LoginResponse, PublicKey,
-	RegistrationFinalization, RegistrationRequest, RegistrationResponse, Result,
+	crypto,
+	locked::Locked,
+	Config, Error, LoginFinalization, LoginRequest, LoginResponse, PasswordPolicy, PublicKey,
+	RegistrationFinalization, RegistrationRequest, RegistrationResponse, Result, SessionKey,
 };
 
 /// Server configuration. This contains the secret key needed to create and use
 /// [`ServerFile`]s, if it is lost, all corresponding [`ServerFile`]s become
 /// unusable.
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+///
+/// Only the [`Config`], the private key and OPRF seed and the settings are
+/// serialized, all additional server keys are derived from them.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(from = "StoredServerConfig")]
 pub struct ServerConfig {
 	/// [`Config`] of this [`ServerConfig`].
 	config: Config,
 	/// Holds the private key and OPRF seed.
-	setup: ServerSetup,
+	setup: Locked<ServerSetup>,
+	/// Registration requires an [`Invitation`](crate::Invitation).
+	invitation_required: bool,
+	/// [`PasswordPolicy`] sent to clients during registration.
+	policy: PasswordPolicy,
+	/// [`ServerFile`] of a random password, used in place of a missing
+	/// [`ServerFile`] during login. Created anew when deserializing.
+	#[serde(skip_serializing)]
+	fake: ServerFile,
 }
 
-impl Default for ServerConfig {
-	fn default() -> Self {
-		let config = Config::default();
+/// Serialized form of [`ServerConfig`].
+#[derive(Deserialize)]
+struct StoredServerConfig {
+	/// [`Config`] of the [`ServerConfig`].
+	config: Config,
+	/// Holds the private key and OPRF seed.
+	setup: Locked<ServerSetup>,
+	/// Registration requires an [`Invitation`](crate::Invitation).
+	invitation_required: bool,
+	/// [`PasswordPolicy`] sent to clients during registration.
+	policy: PasswordPolicy,
+}
+
+impl From<StoredServerConfig> for ServerConfig {
+	fn from(stored: StoredServerConfig) -> Self {
+		let fake = fake_file(stored.config, &stored.setup);
 
 		Self {
-			config,
-			setup: ServerSetup::new(config.cipher_suite),
+			config: stored.config,
+			setup: stored.setup,
+			invitation_required: stored.invitation_required,
+			policy: stored.policy,
+			fake,
 		}
 	}
 }
 
+impl Default for ServerConfig {
+	fn default() -> Self {
+		Self::new(Config::default())
+	}
+}
+
 impl ServerConfig {
 	/// Create a new [`ServerConfig`]. This contains the secret key needed to
 	/// create and use [`ServerFile`]s, if it is lost, all corresponding
 	/// [`ServerFile`]s become unusable.
 	#[must_use]
 	pub fn new(config: Config) -> Self {
+		let setup = ServerSetup::new(config.cipher_suite);
+		let fake = fake_file(config, &setup);
+
 		Self {
 			config,
-			setup: ServerSetup::new(config.cipher_suite),
+			setup: Locked::new(setup),
+			invitation_required: false,
+			policy: PasswordPolicy::NONE,
+			fake,
 		}
 	}
 
+	/// Requires an [`Invitation`](crate::Invitation) for every registration,
+	/// [`ServerRegistration::register()`] is rejected and
+	/// [`ServerRegistration::register_invited()`] has to be used instead.
+	/// Defaults to `false`.
+	#[must_use]
+	pub const fn with_invitation_required(mut self, invitation_required: bool) -> Self {
+		self.invitation_required = invitation_required;
+		self
+	}
+
+	/// Returns `true` if registration requires an
+	/// [`Invitation`](crate::Invitation). See
+	/// [`with_invitation_required()`](Self::with_invitation_required).
+	#[must_use]
+	pub const fn invitation_required(&self) -> bool {
+		self.invitation_required
+	}
+
+	/// Returns a [`ServerConfig`] dictating `policy` to clients during
+	/// registration, see [`PasswordPolicy`]. Changing it doesn't affect
+	/// existing [`ServerFile`]s. Defaults to [`PasswordPolicy::NONE`].
+	#[must_use]
+	pub const fn with_policy(mut self, policy: PasswordPolicy) -> Self {
+		self.policy = policy;
+		self
+	}
+
+	/// Returns the [`PasswordPolicy`] of this [`ServerConfig`]. See
+	/// [`with_policy()`](Self::with_policy).
+	#[must_use]
+	pub const fn policy(&self) -> PasswordPolicy {
+		self.policy
+	}
+
 	/// Returns the [`Config`] associated with this [`ServerConfig`].
 	#[must_use]
 	pub const fn config(&self) -> Config {
@@ -55,6 +135,65 @@ impl ServerConfig {
 	pub fn public_key(&self) -> PublicKey {
 		PublicKey::new(self.config(), self.setup.public_key())
 	}
+
+	/// Returns a [`ServerConfig`] with the same keys to register and login
+	/// [`RecoveryCode`](crate::RecoveryCode)s. See [`Config::to_recovery()`].
+	///
+	/// [`RecoveryCode`](crate::RecoveryCode)s are registered by already
+	/// registered users, so no [`Invitation`](crate::Invitation) is required.
+	#[must_use]
+	pub fn to_recovery(&self) -> Self {
+		Self {
+			config: self.config.to_recovery(),
+			setup: self.setup.clone(),
+			invitation_required: false,
+			policy: PasswordPolicy::NONE,
+			fake: self.fake.clone(),
+		}
+	}
+
+	/// Returns `true` if the secrets of this [`ServerConfig`] are locked in
+	/// memory, see the `locked-memory` crate feature. Always `false` on
+	/// non-Unix platforms, where the feature has no effect.
+	#[must_use]
+	pub const fn is_locked(&self) -> bool {
+		self.setup.is_locked()
+	}
+
+	/// Derives a key for a specific purpose from the private key and OPRF seed
+	/// of this [`ServerConfig`].
+	pub(crate) fn derive_key(&self, info: &[u8]) -> Zeroizing<[u8; 32]> {
+		self.setup.derive_key(info)
+	}
+}
+
+impl ConstantTimeEq for ServerConfig {
+	fn ct_eq(&self, other: &Self) -> Choice {
+		Choice::from(u8::from(
+			self.config == other.config
+				&& self.invitation_required == other.invitation_required
+				&& self.policy == other.policy,
+		)) & self.setup.ct_eq(&other.setup)
+	}
+}
+
+impl PartialEq for ServerConfig {
+	fn eq(&self, other: &Self) -> bool {
+		self.ct_eq(other).into()
+	}
+}
+
+impl Eq for ServerConfig {}
+
+impl Debug for ServerConfig {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ServerConfig")
+			.field("config", &self.config)
+			.field("public_key", &self.public_key())
+			.field("invitation_required", &self.invitation_required)
+			.field("policy", &self.policy)
+			.finish_non_exhaustive()
+	}
 }
 
 /// Holds the state of a registration process. See [`register`](Self::register).
@@ -87,12 +226,30 @@ impl ServerRegistration {
 	/// [`ClientRegistration::finish()`](crate::ClientRegistration::finish).
 	///
 	/// # Errors
+	/// - [`Error::InvitationMissing`] if the [`ServerConfig`] requires an
+	///   [`Invitation`](crate::Invitation), see
+	///   [`ServerConfig::with_invitation_required()`]
 	/// - [`Error::Config`] if [`ServerConfig`] and [`RegistrationRequest`] were
 	///   not created with the same [`Config`]
 	/// - [`Error::Opaque`] on internal OPAQUE error
 	pub fn register(
 		config: &ServerConfig,
 		request: RegistrationRequest,
+	) -> Result<(Self, RegistrationResponse)> {
+		if config.invitation_required {
+			return Err(Error::InvitationMissing);
+		}
+
+		Self::register_unchecked(config, request)
+	}
+
+	/// Starts the registration process like [`register()`](Self::register),
+	/// without checking [`ServerConfig::invitation_required()`]. Used when the
+	/// registration is authorized otherwise, e.g. by an
+	/// [`Invitation`](crate::Invitation).
+	pub(crate) fn register_unchecked(
+		config: &ServerConfig,
+		request: RegistrationRequest,
 	) -> Result<(Self, RegistrationResponse)> {
 		if config.config != request.config {
 			return Err(Error::Config);
@@ -110,6 +267,7 @@ impl ServerRegistration {
 			RegistrationResponse {
 				config: config.config,
 				message,
+				policy: config.policy,
 			},
 		))
 	}
@@ -131,8 +289,64 @@ impl ServerRegistration {
 			config: self.config,
 			public_key: self.public_key,
 			file,
+			status: PasswordStatus::Permanent,
 		})
 	}
+
+	/// Finishes the registration process like [`finish()`](Self::finish), but
+	/// marks the password as temporary, e.g. when an administrator provisions
+	/// the account. [`ServerLogin::finish()`] will report
+	/// [`PasswordStatus::Temporary`] until the client registers a new password.
+	///
+	/// # Errors
+	/// [`Error::Config`] if [`ServerConfig`] and [`RegistrationRequest`] were
+	/// not created with the same [`Config`].
+	pub fn finish_temporary(self, finalization: RegistrationFinalization) -> Result<ServerFile> {
+		let mut file = self.finish(finalization)?;
+		file.status = PasswordStatus::Temporary;
+
+		Ok(file)
+	}
+
+	/// Finishes the registration process without revealing to the client if
+	/// it was already registered. Pass the [`ServerFile`] of the client if it
+	/// exists, pass [`None`] otherwise. The protocol completes the same way in
+	/// both cases, the client should be notified out of band.
+	///
+	/// An existing [`ServerFile`] is never replaced, see
+	/// [`RegistrationOutcome`].
+	///
+	/// # Errors
+	/// [`Error::Config`] if [`ServerConfig`], [`RegistrationRequest`] or the
+	/// existing [`ServerFile`] were not created with the same [`Config`].
+	pub fn finish_private(
+		self,
+		finalization: RegistrationFinalization,
+		existing: Option<&ServerFile>,
+	) -> Result<RegistrationOutcome> {
+		if matches!(existing, Some(file) if file.config != self.config) {
+			return Err(Error::Config);
+		}
+
+		let file = self.finish(finalization)?;
+
+		if existing.is_some() {
+			Ok(RegistrationOutcome::Existing)
+		} else {
+			Ok(RegistrationOutcome::Registered(file))
+		}
+	}
+}
+
+/// Outcome of [`ServerRegistration::finish_private()`], only known to the
+/// server.
+#[must_use = "Store the `ServerFile` or notify the client"]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RegistrationOutcome {
+	/// Client wasn't registered before, store the [`ServerFile`].
+	Registered(ServerFile),
+	/// Client was already registered, the existing [`ServerFile`] stays valid.
+	Existing,
 }
 
 /// Represents a registered client, this is needed for the client to login. See
@@ -146,6 +360,20 @@ pub struct ServerFile {
 	public_key: PublicKey,
 	/// Password envelope.
 	file: cipher_suite::ServerFile,
+	/// [`PasswordStatus`] set during registration.
+	status: PasswordStatus,
+}
+
+/// Status of the password of a [`ServerFile`], reported by
+/// [`ServerLogin::finish()`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum PasswordStatus {
+	/// Password was chosen by the client.
+	Permanent,
+	/// Password was provisioned by an administrator and has to be changed
+	/// before issuing a real session. See
+	/// [`ServerRegistration::finish_temporary()`].
+	Temporary,
 }
 
 impl ServerFile {
@@ -160,10 +388,16 @@ impl ServerFile {
 	pub const fn public_key(&self) -> PublicKey {
 		self.public_key
 	}
+
+	/// Returns the [`PasswordStatus`] of this [`ServerFile`].
+	#[must_use]
+	pub const fn password_status(&self) -> PasswordStatus {
+		self.status
+	}
 }
 
 /// Starts the login process on the server.
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
 #[must_use = "Does nothing if not `finish`ed"]
 pub struct ServerLogin {
 	/// [`Config`] of the corresponding [`ServerConfig`].
@@ -172,6 +406,8 @@ pub struct ServerLogin {
 	public_key: PublicKey,
 	/// Login process state.
 	state: cipher_suite::ServerLogin,
+	/// [`PasswordStatus`] of the [`ServerFile`].
+	status: PasswordStatus,
 }
 
 impl ServerLogin {
@@ -194,7 +430,9 @@ impl ServerLogin {
 	/// If a client is registered, pass the appropriate [`ServerFile`], pass
 	/// [`None`] otherwise. Passing [`None`] simulates a login attempt in a way
 	/// that doesn't let an attacker determine if a corresponding client is
-	/// registered or not.
+	/// registered or not. Both cases do the same work, a [`ServerFile`] of a
+	/// random password stored in the [`ServerConfig`] is used in place of the
+	/// missing one.
 	///
 	/// # Errors
 	/// - [`Error::Config`] if [`ServerConfig`], [`ServerFile`] or
@@ -206,15 +444,31 @@ impl ServerLogin {
 		config: &ServerConfig,
 		file: Option<ServerFile>,
 		request: LoginRequest,
+	) -> Result<(Self, LoginResponse)> {
+		Self::login_with_context(config, file, request, &[])
+	}
+
+	/// [`login()`](Self::login) binding `context` into the transcript, the
+	/// client has to pass the same `context` to
+	/// [`ClientLogin::finish_with_context()`](crate::ClientLogin::finish_with_context).
+	#[allow(clippy::needless_pass_by_value)]
+	pub(crate) fn login_with_context(
+		config: &ServerConfig,
+		file: Option<ServerFile>,
+		request: LoginRequest,
+		context: &[u8],
 	) -> Result<(Self, LoginResponse)> {
 		if config.config != request.config {
 			return Err(Error::Config);
 		}
 
+		let file = file.as_ref().unwrap_or(&config.fake);
+
 		let (state, message) = cipher_suite::ServerLogin::login(
 			&config.setup,
-			file.map(|file| (file.file, file.public_key.key)),
+			(&file.file, file.public_key.key),
 			request.message,
+			context,
 		)?;
 
 		Ok((
@@ -222,6 +476,7 @@ impl ServerLogin {
 				config: config.config,
 				public_key: config.public_key(),
 				state,
+				status: file.status,
 			},
 			LoginResponse {
 				config: config.config,
@@ -230,15 +485,60 @@ impl ServerLogin {
 		))
 	}
 
-	/// Finishes the login process.
+	/// Finishes the login process. The returned [`SessionKey`] is shared with
+	/// the client, see [`SecureChannel`](crate::SecureChannel).
+	///
+	/// If [`PasswordStatus::Temporary`] is returned, the client should be
+	/// forced to register a new password before issuing a real session.
 	///
 	/// # Errors
 	/// [`Error::Opaque`] on internal OPAQUE error.
-	pub fn finish(self, finalization: LoginFinalization) -> Result<()> {
+	pub fn finish(self, finalization: LoginFinalization) -> Result<(SessionKey, PasswordStatus)> {
 		if self.config != finalization.config {
 			return Err(Error::Config);
 		}
 
-		self.state.finish(finalization.message)
+		Ok((
+			SessionKey::new(self.state.finish(finalization.message)?),
+			self.status,
+		))
+	}
+}
+
+impl Debug for ServerLogin {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.debug_struct("ServerLogin")
+			.field("config", &self.config)
+			.field("public_key", &self.public_key)
+			.field("status", &self.status)
+			.finish_non_exhaustive()
+	}
+}
+
+/// Creates the [`ServerFile`] of a random password, see
+/// [`ServerConfig::fake`].
+fn fake_file(config: Config, setup: &ServerSetup) -> ServerFile {
+	// the server side of the record doesn't depend on the MHF, so the cheapest
+	// one is used
+	let recovery = config.to_recovery();
+	let password: Zeroizing<[u8; 32]> = Zeroizing::new(crypto::random());
+
+	let (client, request) =
+		cipher_suite::ClientRegistration::register(recovery.cipher_suite, password.as_ref())
+			.expect("failed to create fake registration");
+	let (server, response) = cipher_suite::ServerRegistration::register(setup, request)
+		.expect("failed to create fake registration");
+	let (finalization, ..) = client
+		.finish(response, &recovery.mhf().to_slow_hash())
+		.expect("failed to create fake registration");
+	let file = server
+		.finish(finalization)
+		.expect("failed to create fake registration");
+
+	ServerFile {
+		config,
+		public_key: PublicKey::new(config, setup.public_key()),
+		file,
+		status: PasswordStatus::Permanent,
 	}
 }
@@ -0,0 +1,44 @@
+//! See [`SessionKey`].
+
+use std::ops::Deref;
+
+use arrayvec::ArrayVec;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::crypto;
+
+/// Secret key shared between client and server after a successful login. Can
+/// be used to establish a [`SecureChannel`](crate::SecureChannel).
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct SessionKey(ArrayVec<u8, 64>);
+
+impl SessionKey {
+	/// Create a [`SessionKey`] from a `[u8; 64]`.
+	pub(crate) const fn new(key: ArrayVec<u8, 64>) -> Self {
+		Self(key)
+	}
+
+	/// Returns an [`ArrayVec`] of this key.
+	#[must_use]
+	pub const fn as_bytes(&self) -> &ArrayVec<u8, 64> {
+		&self.0
+	}
+}
+
+crypto::impl_secret!(SessionKey);
+
+impl AsRef<ArrayVec<u8, 64>> for SessionKey {
+	fn as_ref(&self) -> &ArrayVec<u8, 64> {
+		self.as_bytes()
+	}
+}
+
+impl Deref for SessionKey {
+	type Target = ArrayVec<u8, 64>;
+
+	fn deref(&self) -> &Self::Target {
+		self.as_bytes()
+	}
+}
@@ -0,0 +1,370 @@
+//! Social recovery, splitting access to a [`WrappedKey`] between trustees with
+//! Shamir's secret sharing.
+
+use std::{collections::BTreeSet, convert::TryInto, fmt::Debug, iter};
+
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+	crypto,
+	recipient::{RecipientPublicKey, RecipientSecretKey, Sealed},
+	DataKey, Error, KeySlot, Result, WrappedKey,
+};
+
+/// Info used to encrypt a [`Share`] to a trustee.
+const SHARE_INFO: &[u8] = b"custodian-password share";
+
+/// A share of a split recovery secret, obtained by a trustee from an
+/// [`EncryptedShare`]. See [`WrappedKey::unlock_social()`].
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct Share {
+	/// X-coordinate of this [`Share`], never `0`.
+	index: u8,
+	/// Number of [`Share`]s needed to recombine the recovery secret.
+	threshold: u8,
+	/// Y-coordinates of this [`Share`].
+	value: [u8; 32],
+}
+
+impl Share {
+	/// Returns the index of this [`Share`], unique in its set.
+	#[must_use]
+	pub const fn index(&self) -> u8 {
+		self.index
+	}
+
+	/// Returns the number of [`Share`]s needed to recombine the recovery
+	/// secret.
+	#[must_use]
+	pub const fn threshold(&self) -> u8 {
+		self.threshold
+	}
+}
+
+crypto::impl_secret!(Share, |this, other| {
+	this.index.ct_eq(&other.index)
+		& this.threshold.ct_eq(&other.threshold)
+		& this.value[..].ct_eq(&other.value[..])
+});
+
+/// A [`Share`] encrypted to a trustee. See [`WrappedKey::add_social_slot()`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct EncryptedShare {
+	/// Index of the encrypted [`Share`].
+	index: u8,
+	/// [`RecipientPublicKey`] of the trustee.
+	trustee: RecipientPublicKey,
+	/// Encrypted [`Share`].
+	share: Sealed,
+}
+
+impl EncryptedShare {
+	/// Returns the index of the encrypted [`Share`].
+	#[must_use]
+	pub const fn index(&self) -> u8 {
+		self.index
+	}
+
+	/// Returns the [`RecipientPublicKey`] of the trustee this
+	/// [`EncryptedShare`] was encrypted to.
+	#[must_use]
+	pub const fn trustee(&self) -> RecipientPublicKey {
+		self.trustee
+	}
+
+	/// Decrypts this [`EncryptedShare`] with the trustees
+	/// [`RecipientSecretKey`].
+	///
+	/// # Errors
+	/// [`Error::Decryption`] if `secret_key` doesn't belong to the trustee.
+	pub fn decrypt(&self, secret_key: &RecipientSecretKey) -> Result<Share> {
+		let share = secret_key.decrypt(&[SHARE_INFO, &[self.index]].concat(), &self.share)?;
+		let (threshold, value) = share.split_first().ok_or(Error::Decryption)?;
+
+		Ok(Share {
+			index: self.index,
+			threshold: *threshold,
+			value: value.try_into().map_err(|_| Error::Decryption)?,
+		})
+	}
+}
+
+impl WrappedKey {
+	/// Splits a fresh random recovery secret into one [`Share`] per trustee,
+	/// any `threshold` of which can unlock `data_key` again, see
+	/// [`unlock_social()`](Self::unlock_social). `data_key` is wrapped in
+	/// [`KeySlot::Social`], replacing the previous content.
+	///
+	/// Every split uses a new recovery secret, splitting again, e.g. to remove
+	/// a trustee, revokes all previous [`Share`]s.
+	///
+	/// The returned [`EncryptedShare`]s are in the same order as `trustees`.
+	///
+	/// # Errors
+	/// - [`Error::Threshold`] if `threshold` is `0`, more than the number of
+	///   `trustees` or there are more than 255 `trustees`
+	/// - [`Error::RecipientPublicKey`] if a trustees [`RecipientPublicKey`] is
+	///   invalid
+	pub fn add_social_slot(
+		&mut self,
+		data_key: &DataKey,
+		threshold: u8,
+		trustees: &[RecipientPublicKey],
+	) -> Result<Vec<EncryptedShare>> {
+		if threshold == 0 || usize::from(threshold) > trustees.len() || trustees.len() > 255 {
+			return Err(Error::Threshold);
+		}
+
+		let secret = Zeroizing::new(crypto::random::<32>());
+		let coefficients: Zeroizing<Vec<[u8; 32]>> =
+			Zeroizing::new((1..threshold).map(|_| crypto::random()).collect());
+
+		let shares = trustees
+			.iter()
+			.zip(1..=u8::MAX)
+			.map(|(trustee, index)| {
+				let mut share = Share {
+					index,
+					threshold,
+					value: *secret,
+				};
+
+				for (byte, value) in share.value.iter_mut().enumerate() {
+					*value = evaluate(
+						index,
+						*value,
+						coefficients.iter().map(|coefficient| coefficient[byte]),
+					);
+				}
+
+				let plaintext = Zeroizing::new([&[threshold][..], &share.value].concat());
+
+				Ok(EncryptedShare {
+					index,
+					trustee: *trustee,
+					share: trustee.encrypt(&[SHARE_INFO, &[index]].concat(), &plaintext)?,
+				})
+			})
+			.collect::<Result<_>>()?;
+
+		self.add_slot_with(KeySlot::Social, data_key, secret.as_ref());
+
+		Ok(shares)
+	}
+
+	/// Recombines `shares` and decrypts the [`DataKey`] stored in
+	/// [`KeySlot::Social`].
+	///
+	/// # Errors
+	/// - [`Error::Shares`] if there are less [`Share`]s than needed, or they
+	///   are inconsistent
+	/// - [`Error::KeySlot`] if [`KeySlot::Social`] is empty
+	/// - [`Error::WrappedKey`] if `shares` don't belong to this [`WrappedKey`]
+	pub fn unlock_social(&self, shares: &[Share]) -> Result<DataKey> {
+		let threshold = shares.first().ok_or(Error::Shares)?.threshold;
+
+		if shares.len() < usize::from(threshold)
+			|| shares
+				.iter()
+				.any(|share| share.threshold != threshold || share.index == 0)
+			|| shares
+				.iter()
+				.map(|share| share.index)
+				.collect::<BTreeSet<_>>()
+				.len() != shares.len()
+		{
+			return Err(Error::Shares);
+		}
+
+		let shares = shares.get(..usize::from(threshold)).ok_or(Error::Shares)?;
+		let coefficients = lagrange(&shares.iter().map(Share::index).collect::<Vec<_>>());
+		let mut secret = Zeroizing::new([0; 32]);
+
+		for (byte, value) in secret.iter_mut().enumerate() {
+			*value = shares
+				.iter()
+				.zip(&coefficients)
+				.fold(0, |secret, (share, coefficient)| {
+					secret ^ mul(share.value[byte], *coefficient)
+				});
+		}
+
+		self.unlock_with(KeySlot::Social, secret.as_ref())
+	}
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial, without branching on
+/// secret data.
+#[allow(clippy::integer_arithmetic)]
+fn mul(mut a: u8, mut b: u8) -> u8 {
+	let mut product = 0;
+
+	for _ in 0..8 {
+		product ^= a & 0_u8.wrapping_sub(b & 1);
+		a = (a << 1) ^ (0x1b & 0_u8.wrapping_sub(a >> 7));
+		b >>= 1;
+	}
+
+	product
+}
+
+/// Multiplicative inverse in GF(2^8), `a^254`.
+fn inverse(a: u8) -> u8 {
+	let mut result = 1;
+
+	for _ in 0..254 {
+		result = mul(result, a);
+	}
+
+	result
+}
+
+/// Evaluates the polynomial with the constant `secret` and `coefficients` at
+/// `x`.
+fn evaluate(x: u8, secret: u8, coefficients: impl DoubleEndedIterator<Item = u8>) -> u8 {
+	coefficients
+		.rev()
+		.chain(iter::once(secret))
+		.fold(0, |result, coefficient| mul(result, x) ^ coefficient)
+}
+
+/// Lagrange basis polynomials for `indices` evaluated at `0`.
+fn lagrange(indices: &[u8]) -> Vec<u8> {
+	indices
+		.iter()
+		.map(|x_i| {
+			let (numerator, denominator) = indices
+				.iter()
+				.filter(|x_j| *x_j != x_i)
+				.fold((1, 1), |(numerator, denominator), x_j| {
+					(mul(numerator, *x_j), mul(denominator, x_j ^ x_i))
+				});
+
+			mul(numerator, inverse(denominator))
+		})
+		.collect()
+}
+
+#[test]
+fn gf256() {
+	for a in 1..=u8::MAX {
+		assert_eq!(mul(a, inverse(a)), 1);
+		assert_eq!(mul(a, 1), a);
+		assert_eq!(mul(a, 0), 0);
+	}
+}
+
+#[test]
+fn social() -> anyhow::Result<()> {
+	use arrayvec::ArrayVec;
+
+	use crate::ExportKey;
+
+	let export_key = ExportKey::new(ArrayVec::from([1; 64]));
+	let (mut wrapped_key, data_key) = WrappedKey::new(&export_key);
+	let trustees: Vec<_> = (0..5).map(|_| RecipientSecretKey::generate()).collect();
+	let public_keys: Vec<_> = trustees
+		.iter()
+		.map(RecipientSecretKey::public_key)
+		.collect();
+
+	let encrypted = wrapped_key.add_social_slot(&data_key, 3, &public_keys)?;
+	assert_eq!(encrypted.len(), 5);
+	assert_eq!(
+		encrypted[0]
+			.decrypt(&trustees[1])
+			.map(|share| share.index()),
+		Err(Error::Decryption)
+	);
+
+	let shares = encrypted
+		.iter()
+		.zip(&trustees)
+		.map(|(share, trustee)| share.decrypt(trustee))
+		.collect::<Result<Vec<_>>>()?;
+	assert!(shares.iter().all(|share| share.threshold() == 3));
+
+	// any combination of at least `threshold` shares works
+	assert_eq!(wrapped_key.unlock_social(&shares[..3])?, data_key);
+	assert_eq!(wrapped_key.unlock_social(&shares[2..])?, data_key);
+	assert_eq!(
+		wrapped_key.unlock_social(&[shares[4].clone(), shares[0].clone(), shares[2].clone()])?,
+		data_key
+	);
+	assert_eq!(wrapped_key.unlock_social(&shares)?, data_key);
+
+	// not enough or inconsistent shares
+	assert_eq!(wrapped_key.unlock_social(&shares[..2]), Err(Error::Shares));
+	assert_eq!(wrapped_key.unlock_social(&[]), Err(Error::Shares));
+	assert_eq!(
+		wrapped_key.unlock_social(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]),
+		Err(Error::Shares)
+	);
+
+	// shares of a different split
+	let (mut other_key, _) = WrappedKey::new(&export_key);
+	assert_eq!(other_key.unlock_social(&shares[..3]), Err(Error::KeySlot));
+	other_key.add_social_slot(&data_key, 3, &public_keys)?;
+	assert_eq!(
+		other_key.unlock_social(&shares[..3]),
+		Err(Error::WrappedKey)
+	);
+
+	// splitting again revokes previous shares
+	let mut resplit = wrapped_key.clone();
+	let encrypted_again = resplit.add_social_slot(&data_key, 3, &public_keys[1..])?;
+	assert_eq!(resplit.unlock_social(&shares[..3]), Err(Error::WrappedKey));
+	let shares_again = encrypted_again
+		.iter()
+		.zip(&trustees[1..])
+		.map(|(share, trustee)| share.decrypt(trustee))
+		.collect::<Result<Vec<_>>>()?;
+	assert_eq!(resplit.unlock_social(&shares_again[..3])?, data_key);
+
+	let share: EncryptedShare = bincode::deserialize(&bincode::serialize(&encrypted[0])?)?;
+	assert_eq!(share.decrypt(&trustees[0])?, shares[0]);
+
+	Ok(())
+}
+
+#[test]
+fn threshold() -> anyhow::Result<()> {
+	use arrayvec::ArrayVec;
+
+	use crate::ExportKey;
+
+	let export_key = ExportKey::new(ArrayVec::from([1; 64]));
+	let (mut wrapped_key, data_key) = WrappedKey::new(&export_key);
+	let trustee = RecipientSecretKey::generate();
+
+	for (threshold, trustees) in [(0, 1), (2, 1), (1, 256)] {
+		assert_eq!(
+			wrapped_key
+				.add_social_slot(&data_key, threshold, &vec![trustee.public_key(); trustees]),
+			Err(Error::Threshold)
+		);
+	}
+
+	// a threshold of `1` and `trustees` are edge cases that have to work
+	for (threshold, trustees) in [(1, 1), (1, 3), (3, 3), (255, 255)] {
+		let shares = wrapped_key
+			.add_social_slot(&data_key, threshold, &vec![trustee.public_key(); trustees])?
+			.iter()
+			.map(|share| share.decrypt(&trustee))
+			.collect::<Result<Vec<_>>>()?;
+
+		assert_eq!(wrapped_key.unlock_social(&shares)?, data_key);
+		assert_eq!(
+			wrapped_key.unlock_social(&shares[trustees - usize::from(threshold)..])?,
+			data_key
+		);
+		assert_eq!(
+			wrapped_key.unlock_social(&shares[..usize::from(threshold) - 1]),
+			Err(Error::Shares)
+		);
+	}
+
+	Ok(())
+}
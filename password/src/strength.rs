@@ -0,0 +1,323 @@
+//! Password strength estimation with [zxcvbn](zxcvbn_), see [`Strength`].
+
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Only this many characters are estimated, longer passwords aren't
+/// realistically guessable anyway and slow down zxcvbn.
+const MAX_LENGTH: usize = 100;
+
+/// Strength of a password, from [`Score::VeryWeak`] to [`Score::VeryStrong`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Score {
+	/// Less than 10^3 guesses, too guessable.
+	VeryWeak,
+	/// Less than 10^6 guesses, protects from throttled online attacks.
+	Weak,
+	/// Less than 10^8 guesses, protects from unthrottled online attacks.
+	Fair,
+	/// Less than 10^10 guesses, moderate protection from offline attacks.
+	Strong,
+	/// 10^10 guesses or more, strong protection from offline attacks.
+	VeryStrong,
+}
+
+impl Default for Score {
+	fn default() -> Self {
+		Self::VeryWeak
+	}
+}
+
+impl Score {
+	/// Converts the score of zxcvbn, from `0` to `4`.
+	const fn from_zxcvbn(score: u8) -> Self {
+		match score {
+			0 => Self::VeryWeak,
+			1 => Self::Weak,
+			2 => Self::Fair,
+			3 => Self::Strong,
+			_ => Self::VeryStrong,
+		}
+	}
+}
+
+/// Estimated strength of a password by [zxcvbn](zxcvbn_). The estimation runs
+/// entirely on the client, the password never has to reach the server.
+///
+/// Passwords are matched against common passwords, English words and names,
+/// user-specific words, keyboard patterns, repeats, sequences, years and
+/// dates. The number of guesses is estimated from the least guessable
+/// combination of these patterns.
+///
+/// Only available with the `zxcvbn` crate feature.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Strength {
+	/// [`Score`] of the password.
+	score: Score,
+	/// Estimated number of guesses.
+	guesses: u64,
+	/// [`Feedback`] to improve the password.
+	feedback: Feedback,
+}
+
+impl Strength {
+	/// Estimates the strength of `password`. `user_inputs` are words specific
+	/// to the user that an attacker is likely to try first, e.g. the user name
+	/// or email address.
+	///
+	/// Only the first 100 characters are considered.
+	#[must_use]
+	pub fn estimate(password: &str, user_inputs: &[&str]) -> Self {
+		let end = password
+			.char_indices()
+			.nth(MAX_LENGTH)
+			.map_or(password.len(), |(index, _)| index);
+
+		password
+			.get(..end)
+			.and_then(|password| zxcvbn_::zxcvbn(password, user_inputs).ok())
+			.map_or_else(
+				// zxcvbn rejects empty passwords
+				|| Self {
+					score: Score::VeryWeak,
+					guesses: 1,
+					feedback: Feedback {
+						warning: None,
+						suggestions: vec![Suggestion::UseFewWords, Suggestion::NoNeedForSymbols],
+					},
+				},
+				|entropy| Self {
+					score: Score::from_zxcvbn(entropy.score()),
+					guesses: entropy.guesses(),
+					feedback: entropy
+						.feedback()
+						.as_ref()
+						.map_or_else(Feedback::default, Feedback::from_zxcvbn),
+				},
+			)
+	}
+
+	/// Returns the [`Score`].
+	#[must_use]
+	pub const fn score(&self) -> Score {
+		self.score
+	}
+
+	/// Returns the estimated number of guesses needed to find the password.
+	#[must_use]
+	pub const fn guesses(&self) -> u64 {
+		self.guesses
+	}
+
+	/// Returns [`Feedback`] to improve the password.
+	#[must_use]
+	pub const fn feedback(&self) -> &Feedback {
+		&self.feedback
+	}
+}
+
+/// Explains why a password is weak and how to improve it. Empty for
+/// passwords with a [`Score`] above [`Score::Fair`].
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Feedback {
+	/// Explains what's wrong with the password.
+	warning: Option<Warning>,
+	/// Suggestions to improve the password.
+	suggestions: Vec<Suggestion>,
+}
+
+impl Feedback {
+	/// Converts the [`Feedback`](zxcvbn_::feedback::Feedback) of zxcvbn.
+	fn from_zxcvbn(feedback: &zxcvbn_::feedback::Feedback) -> Self {
+		use zxcvbn_::feedback::{Suggestion as ZxcvbnSuggestion, Warning as ZxcvbnWarning};
+
+		let warning = feedback.warning().map(|warning| match warning {
+			ZxcvbnWarning::ThisIsATop10Password => Warning::TopTen,
+			ZxcvbnWarning::ThisIsATop100Password => Warning::TopHundred,
+			ZxcvbnWarning::ThisIsACommonPassword => Warning::Common,
+			ZxcvbnWarning::ThisIsSimilarToACommonlyUsedPassword => Warning::SimilarToCommon,
+			ZxcvbnWarning::AWordByItselfIsEasyToGuess => Warning::Word,
+			ZxcvbnWarning::NamesAndSurnamesByThemselvesAreEasyToGuess
+			| ZxcvbnWarning::CommonNamesAndSurnamesAreEasyToGuess => Warning::Name,
+			ZxcvbnWarning::StraightRowsOfKeysAreEasyToGuess
+			| ZxcvbnWarning::ShortKeyboardPatternsAreEasyToGuess => Warning::KeyboardPattern,
+			ZxcvbnWarning::RepeatsLikeAaaAreEasyToGuess
+			| ZxcvbnWarning::RepeatsLikeAbcAbcAreOnlySlightlyHarderToGuess => Warning::Repeat,
+			ZxcvbnWarning::SequencesLikeAbcAreEasyToGuess => Warning::Sequence,
+			ZxcvbnWarning::RecentYearsAreEasyToGuess => Warning::RecentYear,
+			ZxcvbnWarning::DatesAreOftenEasyToGuess => Warning::Date,
+		});
+
+		let mut suggestions = Vec::new();
+
+		for suggestion in feedback.suggestions() {
+			let suggestion = match suggestion {
+				ZxcvbnSuggestion::UseAFewWordsAvoidCommonPhrases => Suggestion::UseFewWords,
+				ZxcvbnSuggestion::NoNeedForSymbolsDigitsOrUppercaseLetters =>
+					Suggestion::NoNeedForSymbols,
+				ZxcvbnSuggestion::AddAnotherWordOrTwo => Suggestion::AddWord,
+				ZxcvbnSuggestion::CapitalizationDoesntHelpVeryMuch =>
+					Suggestion::CapitalizationDoesntHelp,
+				ZxcvbnSuggestion::AllUppercaseIsAlmostAsEasyToGuessAsAllLowercase =>
+					Suggestion::AllUppercase,
+				ZxcvbnSuggestion::ReversedWordsArentMuchHarderToGuess => Suggestion::ReversedWords,
+				ZxcvbnSuggestion::PredictableSubstitutionsDontHelpVeryMuch =>
+					Suggestion::PredictableSubstitutions,
+				ZxcvbnSuggestion::UseALongerKeyboardPatternWithMoreTurns =>
+					Suggestion::LongerKeyboardPattern,
+				ZxcvbnSuggestion::AvoidRepeatedWordsAndCharacters => Suggestion::AvoidRepeats,
+				ZxcvbnSuggestion::AvoidSequences => Suggestion::AvoidSequences,
+				ZxcvbnSuggestion::AvoidYearsThatAreAssociatedWithYou
+				| ZxcvbnSuggestion::AvoidDatesAndYearsThatAreAssociatedWithYou
+				| ZxcvbnSuggestion::AvoidRecentYears => Suggestion::AvoidYears,
+			};
+
+			if !suggestions.contains(&suggestion) {
+				suggestions.push(suggestion);
+			}
+		}
+
+		Self {
+			warning,
+			suggestions,
+		}
+	}
+
+	/// Returns the [`Warning`] explaining what's wrong with the password.
+	#[must_use]
+	pub const fn warning(&self) -> Option<Warning> {
+		self.warning
+	}
+
+	/// Returns [`Suggestion`]s to improve the password.
+	#[must_use]
+	pub fn suggestions(&self) -> &[Suggestion] {
+		&self.suggestions
+	}
+}
+
+/// Explains what's wrong with a password, see [`Feedback::warning()`]. Use
+/// [`Display`] for an English message.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Warning {
+	/// Password is one of the ten most common passwords.
+	TopTen,
+	/// Password is one of the hundred most common passwords.
+	TopHundred,
+	/// Password is a common password.
+	Common,
+	/// Password is similar to a common password.
+	SimilarToCommon,
+	/// Password is a single word.
+	Word,
+	/// Password is a common name.
+	Name,
+	/// Password contains a run of adjacent keys.
+	KeyboardPattern,
+	/// Password contains repeated characters.
+	Repeat,
+	/// Password contains a sequence.
+	Sequence,
+	/// Password contains a recent year.
+	RecentYear,
+	/// Password contains a date.
+	Date,
+}
+
+impl Display for Warning {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::TopTen => "This is a top-10 common password",
+			Self::TopHundred => "This is a top-100 common password",
+			Self::Common => "This is a very common password",
+			Self::SimilarToCommon => "This is similar to a commonly used password",
+			Self::Word => "A word by itself is easy to guess",
+			Self::Name => "Names and surnames by themselves are easy to guess",
+			Self::KeyboardPattern => "Straight rows of keys are easy to guess",
+			Self::Repeat => "Repeats like \"aaa\" or \"abcabc\" are easy to guess",
+			Self::Sequence => "Sequences like \"abc\" or \"6543\" are easy to guess",
+			Self::RecentYear => "Recent years are easy to guess",
+			Self::Date => "Dates are often easy to guess",
+		})
+	}
+}
+
+/// Suggestion to improve a password, see [`Feedback::suggestions()`]. Use
+/// [`Display`] for an English message.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum Suggestion {
+	/// Use multiple uncommon words.
+	UseFewWords,
+	/// Symbols, digits and uppercase letters aren't required.
+	NoNeedForSymbols,
+	/// Add more words.
+	AddWord,
+	/// Capitalizing the first letter doesn't help.
+	CapitalizationDoesntHelp,
+	/// Uppercasing everything doesn't help.
+	AllUppercase,
+	/// Reversing words doesn't help.
+	ReversedWords,
+	/// Substituting letters with digits or symbols doesn't help.
+	PredictableSubstitutions,
+	/// Use longer keyboard patterns with more turns.
+	LongerKeyboardPattern,
+	/// Avoid repeated words and characters.
+	AvoidRepeats,
+	/// Avoid sequences.
+	AvoidSequences,
+	/// Avoid recent years and years associated with the user.
+	AvoidYears,
+}
+
+impl Display for Suggestion {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::UseFewWords => "Use a few words, avoid common phrases",
+			Self::NoNeedForSymbols => "No need for symbols, digits, or uppercase letters",
+			Self::AddWord => "Add another word or two. Uncommon words are better.",
+			Self::CapitalizationDoesntHelp => "Capitalization doesn't help very much",
+			Self::AllUppercase => "All-uppercase is almost as easy to guess as all-lowercase",
+			Self::ReversedWords => "Reversed words aren't much harder to guess",
+			Self::PredictableSubstitutions =>
+				"Predictable substitutions like '@' instead of 'a' don't help very much",
+			Self::LongerKeyboardPattern => "Use a longer keyboard pattern with more turns",
+			Self::AvoidRepeats => "Avoid repeated words and characters",
+			Self::AvoidSequences => "Avoid sequences",
+			Self::AvoidYears => "Avoid recent years and years that are associated with you",
+		})
+	}
+}
+
+#[test]
+fn strength() -> anyhow::Result<()> {
+	let weak = Strength::estimate("password", &[]);
+	assert_eq!(weak.score(), Score::VeryWeak);
+	assert_eq!(weak.feedback().warning(), Some(Warning::TopTen));
+	assert!(weak.guesses() < 1_000);
+
+	// strong passwords have no feedback
+	let random = Strength::estimate("kV8#qz!W2m$Lp", &[]);
+	assert_eq!(random.score(), Score::VeryStrong);
+	assert_eq!(random.feedback(), &Feedback::default());
+
+	// rejected by zxcvbn
+	let empty = Strength::estimate("", &[]);
+	assert_eq!(empty.score(), Score::VeryWeak);
+	assert_eq!(empty.feedback().suggestions(), [
+		Suggestion::UseFewWords,
+		Suggestion::NoNeedForSymbols
+	]);
+
+	// long passwords are truncated
+	let long = "\u{1f600}".repeat(1000);
+	assert_eq!(Strength::estimate(&long, &[]).score(), Score::VeryStrong);
+
+	assert_eq!(
+		bincode::deserialize::<Strength>(&bincode::serialize(&weak)?)?,
+		weak
+	);
+
+	Ok(())
+}
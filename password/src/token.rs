@@ -0,0 +1,294 @@
+//! Signed authentication tokens, see [`Token`].
+
+use std::{
+	convert::{TryFrom, TryInto},
+	fmt::{self, Display, Formatter},
+	str::FromStr,
+	time::{Duration, SystemTime},
+};
+
+use ed25519_dalek::{ExpandedSecretKey, PublicKey, SecretKey, Signature, SIGNATURE_LENGTH};
+use serde::{Deserialize, Serialize};
+
+use crate::{ledger, Config, Error, Result, ServerConfig};
+
+/// HKDF info used to derive the signing key from a [`ServerConfig`].
+const INFO: &[u8] = b"custodian-password token";
+/// Header of every [`Token`].
+const HEADER: &str = "v4.public.";
+
+/// Authentication token signed by the server after a successful login, in the
+/// format of a PASETO `v4.public` token. Can be checked with a
+/// [`TokenVerifier`] without access to the [`ServerConfig`].
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Token(String);
+
+/// Claims signed in a [`Token`], returned by [`TokenVerifier::verify()`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct Claims {
+	/// Subject the [`Token`] was issued to.
+	#[serde(rename = "sub")]
+	subject: String,
+	/// Issue time in seconds since the Unix epoch, encoded as RFC 3339.
+	#[serde(rename = "iat", with = "rfc3339")]
+	issued: u64,
+	/// Expiry in seconds since the Unix epoch, encoded as RFC 3339.
+	#[serde(rename = "exp", with = "rfc3339")]
+	expires: u64,
+	/// [`Config`] of the [`ServerConfig`] that issued the [`Token`].
+	config: Config,
+}
+
+impl Claims {
+	/// Returns the subject the [`Token`] was issued to.
+	#[must_use]
+	pub fn subject(&self) -> &str {
+		&self.subject
+	}
+
+	/// Returns the time the [`Token`] was issued.
+	#[must_use]
+	pub fn issued(&self) -> SystemTime {
+		ledger::from_timestamp(self.issued)
+	}
+
+	/// Returns the expiry of the [`Token`].
+	#[must_use]
+	pub fn expires(&self) -> SystemTime {
+		ledger::from_timestamp(self.expires)
+	}
+
+	/// Returns the [`Config`] of the [`ServerConfig`] that issued the
+	/// [`Token`].
+	#[must_use]
+	pub const fn config(&self) -> Config {
+		self.config
+	}
+}
+
+impl Token {
+	/// Issues a new [`Token`] for `subject`, valid for `validity`. Meant to be
+	/// called after [`ServerLogin::finish()`](crate::ServerLogin::finish).
+	///
+	/// # Errors
+	/// [`Error::Token`] if the claims couldn't be serialized.
+	pub fn issue(config: &ServerConfig, subject: &str, validity: Duration) -> Result<Self> {
+		let issued = ledger::now();
+		let claims = Claims {
+			subject: subject.to_owned(),
+			issued,
+			expires: issued.saturating_add(validity.as_secs()),
+			config: config.config(),
+		};
+		let message = serde_json::to_vec(&claims).map_err(|_| Error::Token)?;
+
+		let secret = signing_key(config);
+		let public = PublicKey::from(&secret);
+		let signature = ExpandedSecretKey::from(&secret).sign(&pae(&message), &public);
+
+		Ok(Self(format!(
+			"{}{}",
+			HEADER,
+			base64::encode_config(
+				[message, signature.to_bytes().to_vec()].concat(),
+				base64::URL_SAFE_NO_PAD
+			)
+		)))
+	}
+
+	/// Returns the encoded [`Token`].
+	#[must_use]
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+}
+
+impl AsRef<str> for Token {
+	fn as_ref(&self) -> &str {
+		self.as_str()
+	}
+}
+
+impl Display for Token {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		formatter.write_str(self.as_str())
+	}
+}
+
+impl FromStr for Token {
+	type Err = Error;
+
+	fn from_str(token: &str) -> Result<Self> {
+		if token.starts_with(HEADER) {
+			Ok(Self(token.to_owned()))
+		} else {
+			Err(Error::Token)
+		}
+	}
+}
+
+/// Verifies [`Token`]s, only needs the public key of the [`ServerConfig`] that
+/// issued them. See [`ServerConfig::token_verifier()`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub struct TokenVerifier([u8; 32]);
+
+impl TokenVerifier {
+	/// Create a [`TokenVerifier`] from its bytes.
+	///
+	/// # Errors
+	/// [`Error::TokenVerifier`] if `bytes` isn't a valid public key.
+	pub fn from_bytes(bytes: [u8; 32]) -> Result<Self> {
+		PublicKey::from_bytes(&bytes).map_err(|_| Error::TokenVerifier)?;
+
+		Ok(Self(bytes))
+	}
+
+	/// Returns the bytes of this [`TokenVerifier`].
+	#[must_use]
+	pub const fn as_bytes(&self) -> &[u8; 32] {
+		&self.0
+	}
+
+	/// Verifies the signature and expiry of a [`Token`] and returns its
+	/// [`Claims`].
+	///
+	/// # Errors
+	/// - [`Error::Token`] if `token` is malformed or wasn't issued by the
+	///   [`ServerConfig`] of this [`TokenVerifier`]
+	/// - [`Error::TokenExpired`] if `token` expired
+	pub fn verify(&self, token: &Token) -> Result<Claims> {
+		let payload = token.0.strip_prefix(HEADER).ok_or(Error::Token)?;
+		let payload =
+			base64::decode_config(payload, base64::URL_SAFE_NO_PAD).map_err(|_| Error::Token)?;
+		let (message, signature) = payload
+			.len()
+			.checked_sub(SIGNATURE_LENGTH)
+			.map(|length| payload.split_at(length))
+			.ok_or(Error::Token)?;
+
+		let public = PublicKey::from_bytes(&self.0).map_err(|_| Error::Token)?;
+		let signature = Signature::try_from(signature).map_err(|_| Error::Token)?;
+		public
+			.verify_strict(&pae(message), &signature)
+			.map_err(|_| Error::Token)?;
+
+		let claims: Claims = serde_json::from_slice(message).map_err(|_| Error::Token)?;
+
+		if ledger::now() >= claims.expires {
+			return Err(Error::TokenExpired);
+		}
+
+		Ok(claims)
+	}
+}
+
+impl ServerConfig {
+	/// Returns the [`TokenVerifier`] for [`Token`]s issued by this
+	/// [`ServerConfig`].
+	#[must_use]
+	pub fn token_verifier(&self) -> TokenVerifier {
+		TokenVerifier(PublicKey::from(&signing_key(self)).to_bytes())
+	}
+}
+
+/// Derives the signing key from a [`ServerConfig`].
+fn signing_key(config: &ServerConfig) -> SecretKey {
+	SecretKey::from_bytes(config.derive_key(INFO).as_ref()).expect("unexpected size")
+}
+
+/// Pre-authentication encoding of the header, `message`, an empty footer and
+/// an empty implicit assertion.
+fn pae(message: &[u8]) -> Vec<u8> {
+	let pieces: [&[u8]; 4] = [HEADER.as_bytes(), message, &[], &[]];
+	let mut output = le64(pieces.len());
+
+	for piece in pieces {
+		output.extend(le64(piece.len()));
+		output.extend_from_slice(piece);
+	}
+
+	output
+}
+
+/// (De)serializes seconds since the Unix epoch as RFC 3339 timestamps, as
+/// required by PASETO.
+mod rfc3339 {
+	use std::convert::TryFrom;
+
+	use serde::{de, ser, Deserialize, Deserializer, Serializer};
+	use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+	/// Serializes `timestamp` as RFC 3339.
+	#[allow(clippy::trivially_copy_pass_by_ref)]
+	pub(super) fn serialize<S: Serializer>(
+		timestamp: &u64,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		let timestamp = i64::try_from(*timestamp)
+			.ok()
+			.and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())
+			.ok_or_else(|| ser::Error::custom("timestamp out of range"))?;
+
+		serializer.serialize_str(&timestamp.format(&Rfc3339).map_err(ser::Error::custom)?)
+	}
+
+	/// Deserializes an RFC 3339 timestamp.
+	pub(super) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+		let timestamp = String::deserialize(deserializer)?;
+		let timestamp = OffsetDateTime::parse(&timestamp, &Rfc3339).map_err(de::Error::custom)?;
+
+		u64::try_from(timestamp.unix_timestamp())
+			.map_err(|_| de::Error::custom("timestamp before the Unix epoch"))
+	}
+}
+
+/// Encodes `length` as a little-endian 64-bit integer.
+fn le64(length: usize) -> Vec<u8> {
+	let length: u64 = length.try_into().expect("length too big");
+	length.to_le_bytes().to_vec()
+}
+
+#[test]
+fn token() -> anyhow::Result<()> {
+	const VALIDITY: Duration = Duration::from_secs(60);
+	let server_config = ServerConfig::default();
+	let verifier = server_config.token_verifier();
+	let verifier: TokenVerifier = bincode::deserialize(&bincode::serialize(&verifier)?)?;
+	assert_eq!(TokenVerifier::from_bytes(*verifier.as_bytes())?, verifier);
+
+	let token = Token::issue(&server_config, "user", VALIDITY)?;
+	assert!(token.as_str().starts_with(HEADER));
+	let token: Token = token.to_string().parse()?;
+
+	let claims = verifier.verify(&token)?;
+	assert_eq!(claims.subject(), "user");
+	let json = serde_json::to_value(&claims)?;
+	assert_eq!(
+		json["iat"].as_str().map(|iat| iat.ends_with('Z')),
+		Some(true)
+	);
+	assert_eq!(serde_json::from_value::<Claims>(json)?, claims);
+	assert_eq!(claims.config(), server_config.config());
+	assert!(claims.issued() <= SystemTime::now());
+	assert!(claims.expires() > SystemTime::now());
+
+	// different server
+	assert_eq!(
+		ServerConfig::default().token_verifier().verify(&token),
+		Err(Error::Token)
+	);
+
+	// tampering
+	let mut tampered = token.as_str().to_owned();
+	tampered.pop();
+	assert_eq!(verifier.verify(&tampered.parse()?), Err(Error::Token));
+	assert_eq!(verifier.verify(&HEADER.parse()?), Err(Error::Token));
+	assert_eq!("v4.local.".parse::<Token>(), Err(Error::Token));
+
+	// expiry
+	let token = Token::issue(&server_config, "user", Duration::ZERO)?;
+	assert_eq!(verifier.verify(&token), Err(Error::TokenExpired));
+
+	Ok(())
+}
@@ -0,0 +1,327 @@
+//! TOTP second factor as defined in RFC 6238, see [`ServerTotp`].
+
+use std::{convert::TryInto, fmt::Debug, time::SystemTime};
+
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use sha1::Sha1;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use crate::{
+	crypto::{self, NONCE_SIZE, TAG_SIZE},
+	ledger, Error, ExportKey, LoginFinalization, PasswordStatus, Result, ServerConfig, ServerLogin,
+	SessionKey,
+};
+
+/// HKDF info used to derive the keys encrypting the [`TotpSecret`].
+const INFO: &[u8] = b"custodian-password totp";
+/// Size of a [`TotpSecret`].
+const SECRET_SIZE: usize = 20;
+/// Time step in seconds.
+const PERIOD: u64 = 30;
+/// Number of digits of a code.
+const DIGITS: u32 = 6;
+/// Number of time steps a code is accepted before or after the current one.
+const SKEW: u64 = 1;
+/// Alphabet of RFC 4648 base32.
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// Secret shared between the users authenticator and the server. Uses
+/// HMAC-SHA1, 6 digits and a period of 30 seconds, which is supported by all
+/// common authenticator apps.
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct TotpSecret([u8; SECRET_SIZE]);
+
+impl TotpSecret {
+	/// Generates a new random [`TotpSecret`].
+	#[must_use]
+	pub fn generate() -> Self {
+		Self(crypto::random())
+	}
+
+	/// Returns the unpadded base32 encoding of this [`TotpSecret`], to be
+	/// entered into an authenticator app.
+	#[must_use]
+	#[allow(clippy::integer_arithmetic)]
+	pub fn to_base32(&self) -> String {
+		let mut output = String::with_capacity(32);
+
+		for chunk in self.0.chunks(5) {
+			let mut bits = 0_u64;
+
+			for byte in chunk {
+				bits = (bits << 8) | u64::from(*byte);
+			}
+
+			for index in (0..8).rev() {
+				#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+				let symbol = ((bits >> (index * 5)) & 0x1f) as usize;
+				output.push(char::from(ALPHABET[symbol]));
+			}
+		}
+
+		output
+	}
+
+	/// Returns the code for the current time.
+	#[must_use]
+	pub fn code(&self) -> u32 {
+		self.code_at(SystemTime::now())
+	}
+
+	/// Returns the code for `time`.
+	#[must_use]
+	pub fn code_at(&self, time: SystemTime) -> u32 {
+		hotp(&self.0, step(time))
+	}
+}
+
+crypto::impl_secret!(TotpSecret);
+
+/// [`TotpSecret`] encrypted with a key derived from the users [`ExportKey`].
+/// Meant to be stored on the server, only the users device can decrypt it.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct EncryptedTotp {
+	/// Nonce used to encrypt the [`TotpSecret`].
+	nonce: [u8; NONCE_SIZE],
+	/// Encrypted [`TotpSecret`].
+	#[serde(with = "BigArray")]
+	secret: [u8; SECRET_SIZE + TAG_SIZE],
+}
+
+impl EncryptedTotp {
+	/// Encrypts `secret` with a key derived from `export_key`.
+	#[must_use]
+	pub fn new(secret: &TotpSecret, export_key: &ExportKey) -> Self {
+		let (nonce, secret) = seal(
+			&crypto::derive_key(export_key.as_slice(), &[], INFO),
+			&secret.0,
+		);
+
+		Self { nonce, secret }
+	}
+
+	/// Decrypts the [`TotpSecret`].
+	///
+	/// # Errors
+	/// [`Error::TotpSecret`] if `export_key` doesn't belong to this
+	/// [`EncryptedTotp`].
+	pub fn decrypt(&self, export_key: &ExportKey) -> Result<TotpSecret> {
+		open(
+			&crypto::derive_key(export_key.as_slice(), &[], INFO),
+			&self.nonce,
+			&self.secret,
+		)
+		.ok_or(Error::TotpSecret)
+	}
+}
+
+/// Server side state to verify codes of a [`TotpSecret`]. The [`TotpSecret`]
+/// is encrypted with a key derived from the [`ServerConfig`].
+///
+/// Every time step can only be used once, make sure to store the updated
+/// [`ServerTotp`] after every successful [`verify()`](Self::verify).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ServerTotp {
+	/// Nonce used to encrypt the [`TotpSecret`].
+	nonce: [u8; NONCE_SIZE],
+	/// Encrypted [`TotpSecret`].
+	#[serde(with = "BigArray")]
+	secret: [u8; SECRET_SIZE + TAG_SIZE],
+	/// Last time step that was successfully used.
+	last_step: Option<u64>,
+}
+
+impl ServerTotp {
+	/// Enrolls a [`TotpSecret`] received from the client.
+	#[must_use]
+	pub fn new(config: &ServerConfig, secret: &TotpSecret) -> Self {
+		let (nonce, secret) = seal(&config.derive_key(INFO), &secret.0);
+
+		Self {
+			nonce,
+			secret,
+			last_step: None,
+		}
+	}
+
+	/// Verifies `code`, accepting one time step of clock skew in both
+	/// directions.
+	///
+	/// # Errors
+	/// - [`Error::ServerTotp`] if [`ServerTotp`] was not created with the same
+	///   [`ServerConfig`]
+	/// - [`Error::TotpReplay`] if the time step of `code` was already used
+	/// - [`Error::Totp`] if `code` is invalid
+	pub fn verify(&mut self, config: &ServerConfig, code: u32) -> Result<()> {
+		let secret =
+			open(&config.derive_key(INFO), &self.nonce, &self.secret).ok_or(Error::ServerTotp)?;
+		let current = step(SystemTime::now());
+
+		let step = (current.saturating_sub(SKEW)..=current.saturating_add(SKEW))
+			.find(|step| {
+				bool::from(
+					hotp(&secret.0, *step)
+						.to_be_bytes()
+						.ct_eq(&code.to_be_bytes()),
+				)
+			})
+			.ok_or(Error::Totp)?;
+
+		if matches!(self.last_step, Some(last_step) if step <= last_step) {
+			return Err(Error::TotpReplay);
+		}
+
+		self.last_step = Some(step);
+
+		Ok(())
+	}
+}
+
+impl ServerLogin {
+	/// Finishes the login process like [`finish()`](Self::finish), but
+	/// additionally requires a valid TOTP `code`. `totp` is only updated if
+	/// the login succeeds.
+	///
+	/// # Errors
+	/// - [`Error::Opaque`] on internal OPAQUE error
+	/// - [`Error::ServerTotp`] if [`ServerTotp`] was not created with the same
+	///   [`ServerConfig`]
+	/// - [`Error::TotpReplay`] if the time step of `code` was already used
+	/// - [`Error::Totp`] if `code` is invalid
+	pub fn finish_with_totp(
+		self,
+		config: &ServerConfig,
+		finalization: LoginFinalization,
+		totp: &mut ServerTotp,
+		code: u32,
+	) -> Result<(SessionKey, PasswordStatus)> {
+		let result = self.finish(finalization)?;
+		totp.verify(config, code)?;
+
+		Ok(result)
+	}
+}
+
+/// Encrypts a [`TotpSecret`] with `key`.
+fn seal(
+	key: &[u8; 32],
+	secret: &[u8; SECRET_SIZE],
+) -> ([u8; NONCE_SIZE], [u8; SECRET_SIZE + TAG_SIZE]) {
+	let (nonce, secret) = crypto::seal(key, INFO, secret);
+
+	(
+		nonce,
+		secret.as_slice().try_into().expect("unexpected size"),
+	)
+}
+
+/// Decrypts a [`TotpSecret`] with `key`.
+fn open(key: &[u8; 32], nonce: &[u8; NONCE_SIZE], secret: &[u8]) -> Option<TotpSecret> {
+	let secret = crypto::open(key, nonce, INFO, secret)?;
+
+	Some(TotpSecret(secret.as_slice().try_into().ok()?))
+}
+
+/// Returns the time step of `time`.
+#[allow(clippy::integer_arithmetic)]
+fn step(time: SystemTime) -> u64 {
+	ledger::to_timestamp(time) / PERIOD
+}
+
+/// HOTP as defined in RFC 4226.
+#[allow(clippy::integer_arithmetic)]
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+	let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("invalid key length");
+	mac.update(&counter.to_be_bytes());
+	let hash = mac.finalize().into_bytes();
+
+	let offset = usize::from(hash[hash.len() - 1] & 0xf);
+	let code = u32::from_be_bytes(
+		hash[offset..offset + 4]
+			.try_into()
+			.expect("unexpected size"),
+	) & 0x7fff_ffff;
+
+	code % 10_u32.pow(DIGITS)
+}
+
+#[test]
+fn hotp_vectors() {
+	use std::time::{Duration, UNIX_EPOCH};
+
+	// RFC 6238 appendix B, truncated to 6 digits
+	let secret = TotpSecret(*b"12345678901234567890");
+	assert_eq!(secret.to_base32(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+
+	for (time, code) in [
+		(59, 287_082),
+		(1_111_111_109, 81_804),
+		(1_111_111_111, 50_471),
+		(1_234_567_890, 5_924),
+		(2_000_000_000, 279_037),
+	] {
+		assert_eq!(secret.code_at(UNIX_EPOCH + Duration::from_secs(time)), code);
+	}
+}
+
+#[test]
+fn totp() -> anyhow::Result<()> {
+	use crate::{ClientConfig, ClientLogin, ClientRegistration, ServerRegistration};
+
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (client_file, finalization, export_key) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	// enrollment
+	let secret = TotpSecret::generate();
+	let encrypted = EncryptedTotp::new(&secret, &export_key);
+	let encrypted: EncryptedTotp = bincode::deserialize(&bincode::serialize(&encrypted)?)?;
+	assert_eq!(encrypted.decrypt(&export_key)?, secret);
+	let mut server_totp = ServerTotp::new(&server_config, &secret);
+	let previous = server_totp.clone();
+
+	// login process
+	let (client, request) = ClientLogin::login(client_config, Some(client_file), PASSWORD)?;
+	let (server, response) = ServerLogin::login(&server_config, Some(server_file), request)?;
+	let (_, finalization, export_key, client_session_key) = client.finish(response)?;
+	let code = encrypted.decrypt(&export_key)?.code();
+	let (server_session_key, _) =
+		server.finish_with_totp(&server_config, finalization, &mut server_totp, code)?;
+	assert_eq!(client_session_key, server_session_key);
+	assert_ne!(server_totp, previous);
+
+	// replay
+	let mut server_totp: ServerTotp = bincode::deserialize(&bincode::serialize(&server_totp)?)?;
+	assert_eq!(
+		server_totp.verify(&server_config, code),
+		Err(Error::TotpReplay)
+	);
+
+	// invalid code
+	let mut totp = previous.clone();
+	assert_eq!(
+		totp.verify(&server_config, (code + 1) % 1_000_000),
+		Err(Error::Totp)
+	);
+	assert_eq!(totp, previous);
+
+	// different server
+	assert_eq!(
+		totp.verify(&ServerConfig::default(), code),
+		Err(Error::ServerTotp)
+	);
+
+	// state without the used time step
+	totp.verify(&server_config, code)?;
+
+	Ok(())
+}
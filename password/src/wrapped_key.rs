@@ -0,0 +1,243 @@
+//! See [`WrappedKey`].
+
+use std::{collections::BTreeMap, convert::TryInto, fmt::Debug};
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+use zeroize::Zeroize;
+
+use crate::{
+	crypto::{self, NONCE_SIZE, TAG_SIZE},
+	Error, ExportKey, Result,
+};
+
+/// HKDF info used to derive key encryption keys.
+const INFO: &[u8] = b"custodian-password wrapped key";
+
+/// Random key used to encrypt user data. Unlike an [`ExportKey`] it doesn't
+/// change with the password, see [`WrappedKey`].
+#[derive(Clone, Deserialize, Serialize, Zeroize)]
+#[zeroize(drop)]
+pub struct DataKey([u8; 32]);
+
+impl DataKey {
+	/// Generates a new random [`DataKey`].
+	fn new() -> Self {
+		Self(crypto::random())
+	}
+
+	/// Returns the bytes of this key.
+	#[must_use]
+	pub const fn as_bytes(&self) -> &[u8; 32] {
+		&self.0
+	}
+}
+
+crypto::impl_secret!(DataKey);
+
+impl AsRef<[u8]> for DataKey {
+	fn as_ref(&self) -> &[u8] {
+		self.as_bytes()
+	}
+}
+
+/// Identifies a slot in a [`WrappedKey`].
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub enum KeySlot {
+	/// Wrapped with the [`ExportKey`] of the users password.
+	Password,
+	/// Wrapped with the [`ExportKey`] of a recovery code.
+	Recovery,
+	/// Wrapped with a secret split between trustees, see
+	/// [`WrappedKey::add_social_slot()`].
+	Social,
+	/// Wrapped with a key escrowed to an administrator, see
+	/// [`WrappedKey::add_escrow_slot()`].
+	Escrow,
+}
+
+impl KeySlot {
+	/// Identifier bound to the encryption of this slot.
+	const fn id(self) -> u8 {
+		match self {
+			Self::Password => 0,
+			Self::Recovery => 1,
+			Self::Social => 2,
+			Self::Escrow => 3,
+		}
+	}
+}
+
+/// A single encrypted copy of the [`DataKey`].
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+struct Slot {
+	/// Salt used to derive the key encryption key.
+	salt: [u8; 32],
+	/// Nonce used to encrypt the [`DataKey`].
+	nonce: [u8; NONCE_SIZE],
+	/// Encrypted [`DataKey`].
+	#[serde(with = "BigArray")]
+	key: [u8; 32 + TAG_SIZE],
+}
+
+/// A [`DataKey`] encrypted under one or more [`ExportKey`]s, similar to LUKS
+/// key slots. This is meant to be stored on the server.
+///
+/// Encrypting user data directly with an [`ExportKey`] means all of it has to
+/// be re-encrypted when the password changes. Encrypting it with a
+/// [`DataKey`] instead only requires the [`WrappedKey`] to be
+/// [rewrapped](Self::rewrap).
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct WrappedKey {
+	/// Occupied slots.
+	slots: BTreeMap<KeySlot, Slot>,
+}
+
+impl WrappedKey {
+	/// Generates a new [`DataKey`] and wraps it with `export_key` in
+	/// [`KeySlot::Password`].
+	#[must_use]
+	pub fn new(export_key: &ExportKey) -> (Self, DataKey) {
+		let data_key = DataKey::new();
+		let mut wrapped_key = Self {
+			slots: BTreeMap::new(),
+		};
+		wrapped_key.add_slot(KeySlot::Password, &data_key, export_key);
+
+		(wrapped_key, data_key)
+	}
+
+	/// Returns all occupied [`KeySlot`]s.
+	pub fn slots(&self) -> impl Iterator<Item = KeySlot> + '_ {
+		self.slots.keys().copied()
+	}
+
+	/// Returns `true` if `slot` is occupied.
+	#[must_use]
+	pub fn has_slot(&self, slot: KeySlot) -> bool {
+		self.slots.contains_key(&slot)
+	}
+
+	/// Decrypts the [`DataKey`] stored in `slot` with `export_key`.
+	///
+	/// # Errors
+	/// - [`Error::KeySlot`] if `slot` is empty
+	/// - [`Error::WrappedKey`] if `export_key` doesn't belong to `slot`
+	pub fn unlock(&self, slot: KeySlot, export_key: &ExportKey) -> Result<DataKey> {
+		self.unlock_with(slot, export_key.as_slice())
+	}
+
+	/// Wraps `data_key` with `export_key` in `slot`, replacing the previous
+	/// content of `slot`.
+	pub fn add_slot(&mut self, slot: KeySlot, data_key: &DataKey, export_key: &ExportKey) {
+		self.add_slot_with(slot, data_key, export_key.as_slice());
+	}
+
+	/// Removes `slot`. Returns `false` if `slot` was empty.
+	///
+	/// # Caution
+	/// Removing the last slot makes the [`DataKey`] unrecoverable.
+	pub fn remove_slot(&mut self, slot: KeySlot) -> bool {
+		self.slots.remove(&slot).is_some()
+	}
+
+	/// Re-wraps `slot` from `old` to `new`, e.g. after a password change.
+	///
+	/// # Errors
+	/// - [`Error::KeySlot`] if `slot` is empty
+	/// - [`Error::WrappedKey`] if `old` doesn't belong to `slot`
+	pub fn rewrap(&mut self, slot: KeySlot, old: &ExportKey, new: &ExportKey) -> Result<()> {
+		let data_key = self.unlock(slot, old)?;
+		self.add_slot(slot, &data_key, new);
+
+		Ok(())
+	}
+
+	/// Decrypts the [`DataKey`] stored in `slot` with arbitrary key material.
+	pub(crate) fn unlock_with(&self, slot: KeySlot, ikm: &[u8]) -> Result<DataKey> {
+		let content = self.slots.get(&slot).ok_or(Error::KeySlot)?;
+		let kek = crypto::derive_key(ikm, &content.salt, &[INFO, &[slot.id()]].concat());
+		let key = crypto::open(&kek, &content.nonce, &[slot.id()], &content.key)
+			.ok_or(Error::WrappedKey)?;
+
+		Ok(DataKey(
+			key.as_slice().try_into().map_err(|_| Error::WrappedKey)?,
+		))
+	}
+
+	/// Wraps `data_key` in `slot` with arbitrary key material.
+	pub(crate) fn add_slot_with(&mut self, slot: KeySlot, data_key: &DataKey, ikm: &[u8]) {
+		let salt = crypto::random();
+		let kek = crypto::derive_key(ikm, &salt, &[INFO, &[slot.id()]].concat());
+		let (nonce, key) = crypto::seal(&kek, &[slot.id()], data_key.as_bytes());
+
+		self.slots.insert(slot, Slot {
+			salt,
+			nonce,
+			key: key.as_slice().try_into().expect("unexpected size"),
+		});
+	}
+}
+
+#[test]
+fn wrapped_key() -> anyhow::Result<()> {
+	use arrayvec::ArrayVec;
+
+	let password = ExportKey::new(ArrayVec::from([1; 64]));
+	let recovery = ExportKey::new(ArrayVec::from([2; 64]));
+	let new_password = ExportKey::new(ArrayVec::from([3; 64]));
+
+	let (mut wrapped_key, data_key) = WrappedKey::new(&password);
+	assert_eq!(wrapped_key.unlock(KeySlot::Password, &password)?, data_key);
+	assert_eq!(
+		wrapped_key.unlock(KeySlot::Recovery, &recovery),
+		Err(Error::KeySlot)
+	);
+
+	wrapped_key.add_slot(KeySlot::Recovery, &data_key, &recovery);
+	assert_eq!(wrapped_key.unlock(KeySlot::Recovery, &recovery)?, data_key);
+	assert_eq!(
+		wrapped_key.unlock(KeySlot::Recovery, &password),
+		Err(Error::WrappedKey)
+	);
+	assert_eq!(wrapped_key.slots().collect::<Vec<_>>(), [
+		KeySlot::Password,
+		KeySlot::Recovery
+	]);
+
+	wrapped_key.rewrap(KeySlot::Password, &password, &new_password)?;
+	assert_eq!(
+		wrapped_key.unlock(KeySlot::Password, &password),
+		Err(Error::WrappedKey)
+	);
+	assert_eq!(
+		wrapped_key.unlock(KeySlot::Password, &new_password)?,
+		data_key
+	);
+	assert_eq!(
+		wrapped_key.rewrap(KeySlot::Password, &password, &new_password),
+		Err(Error::WrappedKey)
+	);
+
+	let wrapped_key: WrappedKey = bincode::deserialize(&bincode::serialize(&wrapped_key)?)?;
+	assert_eq!(wrapped_key.unlock(KeySlot::Recovery, &recovery)?, data_key);
+
+	Ok(())
+}
+
+#[test]
+fn remove_slot() {
+	use arrayvec::ArrayVec;
+
+	let password = ExportKey::new(ArrayVec::from([1; 64]));
+	let (mut wrapped_key, _) = WrappedKey::new(&password);
+
+	assert!(wrapped_key.has_slot(KeySlot::Password));
+	assert!(wrapped_key.remove_slot(KeySlot::Password));
+	assert!(!wrapped_key.has_slot(KeySlot::Password));
+	assert!(!wrapped_key.remove_slot(KeySlot::Password));
+	assert_eq!(
+		wrapped_key.unlock(KeySlot::Password, &password),
+		Err(Error::KeySlot)
+	);
+}
@@ -0,0 +1,164 @@
+//! Statistical timing test in the style of dudect: measures
+//! [`ServerLogin::login()`] with and without a [`ServerFile`] and compares both
+//! distributions with Welch's t-test.
+//!
+//! A short run with a conservative threshold runs by default and catches
+//! gross regressions. The long run takes long and depends on the load of the
+//! machine, so it is ignored by default, run it with
+//! `cargo test --release -- --ignored`.
+
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use custodian_password::{
+	ClientConfig, ClientLogin, ClientRegistration, LoginRequest, Result, ServerConfig, ServerFile,
+	ServerLogin, ServerRegistration,
+};
+
+/// Measurements above this percentile are discarded, they are dominated by
+/// scheduling noise.
+const PERCENTILE: usize = 90;
+/// Threshold of the t-statistic, dudect considers everything above a timing
+/// leak.
+const THRESHOLD: f64 = 10.;
+/// Threshold of the t-statistic for the short run, leaves room for the noise
+/// of few measurements on a busy machine.
+const SHORT_THRESHOLD: f64 = 20.;
+
+/// Running mean and variance, see Welford's algorithm.
+#[derive(Default)]
+struct Statistic {
+	/// Number of measurements.
+	count: f64,
+	/// Mean of all measurements.
+	mean: f64,
+	/// Sum of squared differences from the mean.
+	m2: f64,
+}
+
+impl Statistic {
+	/// Adds a measurement.
+	fn push(&mut self, value: f64) {
+		self.count += 1.;
+		let delta = value - self.mean;
+		self.mean += delta / self.count;
+		self.m2 += delta * (value - self.mean);
+	}
+
+	/// Returns the sample variance.
+	fn variance(&self) -> f64 {
+		self.m2 / (self.count - 1.)
+	}
+
+	/// Welch's t-statistic between two [`Statistic`]s.
+	fn t(&self, other: &Self) -> f64 {
+		(self.mean - other.mean)
+			/ (self.variance() / self.count + other.variance() / other.count).sqrt()
+	}
+}
+
+/// Xorshift used to pick the class of every measurement, cryptographic quality
+/// isn't needed.
+struct Rng(u64);
+
+impl Rng {
+	/// Seeds the [`Rng`] with the current time.
+	fn new() -> Self {
+		let seed = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.expect("time went backwards")
+			.subsec_nanos();
+
+		Self(u64::from(seed) | 1)
+	}
+
+	/// Returns a random [`bool`].
+	fn bool(&mut self) -> bool {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		self.0 & 1 == 1
+	}
+}
+
+/// Measures a single [`ServerLogin::login()`] in seconds.
+fn measure(config: &ServerConfig, file: Option<ServerFile>, request: LoginRequest) -> Result<f64> {
+	let start = Instant::now();
+	let result = ServerLogin::login(config, file, request);
+	let elapsed = start.elapsed();
+	let (..) = result?;
+
+	Ok(elapsed.as_secs_f64())
+}
+
+/// Measures [`ServerLogin::login()`] `samples` times after `warmup`
+/// discarded measurements, randomly with and without a [`ServerFile`], and
+/// returns Welch's t-statistic between both classes.
+fn t_statistic(samples: usize, warmup: usize) -> anyhow::Result<f64> {
+	const PASSWORD: &[u8] = b"password";
+	let server_config = ServerConfig::default();
+	let client_config = ClientConfig::default();
+
+	let (client, request) = ClientRegistration::register(client_config, PASSWORD)?;
+	let (server, response) = ServerRegistration::register(&server_config, request)?;
+	let (_, finalization, _) = client.finish(response)?;
+	let server_file = server.finish(finalization)?;
+
+	let (_, request) = ClientLogin::login(client_config, None, PASSWORD)?;
+
+	let mut rng = Rng::new();
+	let mut measurements = Vec::with_capacity(samples);
+
+	for index in 0..warmup + samples {
+		let known = rng.bool();
+		let file = known.then(|| server_file.clone());
+		let time = measure(&server_config, file, request.clone())?;
+
+		if index >= warmup {
+			measurements.push((known, time));
+		}
+	}
+
+	let mut sorted: Vec<_> = measurements.iter().map(|(_, time)| *time).collect();
+	sorted.sort_by(|a, b| a.partial_cmp(b).expect("unexpected NaN"));
+	let cutoff = sorted[sorted.len() * PERCENTILE / 100];
+
+	let mut known = Statistic::default();
+	let mut unknown = Statistic::default();
+
+	for (class, time) in measurements {
+		if time <= cutoff {
+			if class {
+				known.push(time);
+			} else {
+				unknown.push(time);
+			}
+		}
+	}
+
+	Ok(known.t(&unknown))
+}
+
+#[test]
+fn unknown_user() -> anyhow::Result<()> {
+	let t = t_statistic(1_000, 100)?;
+	assert!(
+		t.abs() < SHORT_THRESHOLD,
+		"timing differs between known and unknown users: t = {}",
+		t
+	);
+
+	Ok(())
+}
+
+#[test]
+#[ignore]
+fn unknown_user_long() -> anyhow::Result<()> {
+	let t = t_statistic(10_000, 1_000)?;
+	assert!(
+		t.abs() < THRESHOLD,
+		"timing differs between known and unknown users: t = {}",
+		t
+	);
+
+	Ok(())
+}
@@ -1,5 +1,13 @@
+use std::{
+	convert::TryInto,
+	fs::{self, File},
+	io::{BufRead, BufReader},
+	path::{Path, PathBuf},
+};
+
+use custodian_password::BreachFilter;
 use khonsu_tools::universal::{
-	anyhow,
+	anyhow::{self, anyhow, Context},
 	code_coverage::{self, CodeCoverage},
 };
 use structopt::StructOpt;
@@ -7,12 +15,34 @@ use structopt::StructOpt;
 #[derive(StructOpt, Debug)]
 pub enum Commands {
 	GenerateCodeCoverageReport,
+	/// Builds a `BreachFilter` from a "Pwned Passwords" SHA-1 dump with lines
+	/// of `{HASH}:{COUNT}`.
+	BuildBreachFilter {
+		/// Path to the dump.
+		#[structopt(parse(from_os_str))]
+		input: PathBuf,
+		/// Path to write the filter to.
+		#[structopt(parse(from_os_str))]
+		output: PathBuf,
+		/// False positive rate of the filter.
+		#[structopt(long, default_value = "0.001")]
+		false_positive_rate: f64,
+		/// Skip passwords that appeared less often.
+		#[structopt(long, default_value = "1")]
+		min_count: u64,
+	},
 }
 
 fn main() -> anyhow::Result<()> {
 	let command = Commands::from_args();
 	match command {
 		Commands::GenerateCodeCoverageReport => CodeCoverage::<CoverageConfig>::execute(true),
+		Commands::BuildBreachFilter {
+			input,
+			output,
+			false_positive_rate,
+			min_count,
+		} => build_breach_filter(&input, &output, false_positive_rate, min_count),
 	}
 }
 
@@ -23,3 +53,50 @@ impl code_coverage::Config for CoverageConfig {
 		vec![String::from("password/examples/*")]
 	}
 }
+
+fn build_breach_filter(
+	input: &Path,
+	output: &Path,
+	false_positive_rate: f64,
+	min_count: u64,
+) -> anyhow::Result<()> {
+	// count first to size the filter
+	let mut items = 0;
+	read_dump(input, min_count, |_| items += 1)?;
+
+	let mut filter = BreachFilter::new(items, false_positive_rate)?;
+	read_dump(input, min_count, |hash| filter.insert_hash(&hash))?;
+
+	fs::write(output, filter.to_bytes())
+		.with_context(|| format!("failed to write {}", output.display()))?;
+	println!("Wrote {} passwords to {}", items, output.display());
+
+	Ok(())
+}
+
+fn read_dump(path: &Path, min_count: u64, mut f: impl FnMut([u8; 20])) -> anyhow::Result<()> {
+	let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+
+	for (number, line) in BufReader::new(file).lines().enumerate() {
+		let line = line?;
+		let (hash, count) = line
+			.trim()
+			.split_once(':')
+			.ok_or_else(|| anyhow!("malformed line {}", number + 1))?;
+
+		if count.parse::<u64>()? < min_count {
+			continue;
+		}
+
+		let hash = (0..hash.len())
+			.step_by(2)
+			.map(|index| u8::from_str_radix(hash.get(index..index + 2).unwrap_or_default(), 16))
+			.collect::<Result<Vec<_>, _>>()?
+			.try_into()
+			.map_err(|_| anyhow!("malformed hash on line {}", number + 1))?;
+
+		f(hash);
+	}
+
+	Ok(())
+}